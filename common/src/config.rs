@@ -1,21 +1,32 @@
-use eyre::{Result, WrapErr};
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Weekday};
+use eyre::{eyre, Result, WrapErr};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::prices::{round_price, Price, PriceArea};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScheduleConfig {
     /// Descriptive name for what is being controlled
     pub name: String,
 
-    /// Pin to control
-    pub pin: u8,
+    /// Switch to control
+    #[serde(flatten)]
+    pub output: OutputConfig,
+
+    /// Several switches controlled together, e.g. the relays of a 3-phase heater. Used instead of
+    /// `output` if not empty.
+    #[serde(default)]
+    pub outputs: Vec<OutputConfig>,
 
     /// Always on (up to `max_on_hours`) if price is under this limit
-    pub low_limit: Option<f64>,
+    pub low_limit: Option<Limit>,
 
     /// Always off if price is over this limit
-    pub high_limit: Option<f64>,
+    pub high_limit: Option<Limit>,
 
     /// Minimum duration to keep the switch on if price is under `high_limit`
     pub min_on_hours: u32,
@@ -25,6 +36,331 @@ pub struct ScheduleConfig {
 
     /// Minimum consecutive hours to keep the switch on in the middle of the day
     pub min_consecutive_on_hours: Option<u32>,
+
+    /// Maximum consecutive hours to keep the switch off, regardless of `high_limit`
+    pub max_consecutive_off_hours: Option<u32>,
+
+    /// Minimum consecutive hours to keep the switch off in the middle of the day
+    pub min_consecutive_off_hours: Option<u32>,
+
+    /// Maximum number of times the switch is turned on or off per day
+    pub max_switches_per_day: Option<u32>,
+
+    /// Keep the switch on for one uninterrupted run of this many hours at the cheapest time
+    /// within `allowed_hours`, instead of using the price limits
+    pub contiguous_hours: Option<u32>,
+
+    /// How to choose the hours. Defaults to `contiguous` if `contiguous_hours` is set, and
+    /// `limits` otherwise.
+    pub strategy: Option<Strategy>,
+
+    /// Periods of the day in which the switch must be on for a minimum time. The cheapest hours
+    /// of each period are used first, and the rest of `min_on_hours` is taken from the whole day.
+    #[serde(default)]
+    pub periods: Vec<Period>,
+
+    /// Times when the switch may be on. If empty, the switch may be on at any time.
+    #[serde(default)]
+    pub allowed_hours: Vec<TimeRange>,
+
+    /// Times when the switch is always on, regardless of price
+    #[serde(default)]
+    pub forced_on: Vec<TimeRange>,
+
+    /// Times when the switch is always off, regardless of price
+    #[serde(default)]
+    pub forced_off: Vec<TimeRange>,
+
+    /// Once tomorrow's prices are known, optimize the rest of today and tomorrow as one window.
+    /// `min_on_hours` and `max_on_hours` then apply per rolling 24 hours.
+    #[serde(default)]
+    pub rolling_window: bool,
+
+    /// Power of the controlled device in kW
+    pub power_kw: Option<f64>,
+
+    /// Energy needed per day in kWh. Requires `power_kw`, and replaces `min_on_hours` if set.
+    pub required_kwh: Option<f64>,
+
+    /// Schedules with a higher priority are turned on first, and kept on when `max_load_kw` is
+    /// reached
+    #[serde(default)]
+    pub priority: i32,
+}
+
+impl ScheduleConfig {
+    /// Checks the settings that depend on each other
+    pub fn validate(&self) -> Result<()> {
+        if self.required_kwh.is_some() && !self.power_kw.is_some_and(|power_kw| power_kw > 0.0) {
+            return Err(eyre!(
+                "{} needs a positive power_kw when required_kwh is set",
+                self.name
+            ));
+        }
+        if !self.outputs.is_empty() && self.output != OutputConfig::default() {
+            return Err(eyre!(
+                "{}: set either pin and actuator or outputs, not both",
+                self.name
+            ));
+        }
+        if self.outputs().iter().any(|output| {
+            matches!(output.actuator, ActuatorConfig::Gpio(_)) && output.pin.is_none()
+        }) {
+            return Err(eyre!("{}: pin is needed for GPIO outputs", self.name));
+        }
+        let period_hours = self
+            .periods
+            .iter()
+            .map(|period| period.min_on_hours)
+            .sum::<u32>();
+        if period_hours > self.max_on_hours {
+            return Err(eyre!(
+                "{}: min_on_hours of the periods ({}) exceed max_on_hours ({})",
+                self.name,
+                period_hours,
+                self.max_on_hours
+            ));
+        }
+        Ok(())
+    }
+
+    /// Switches controlled by the schedule
+    pub fn outputs(&self) -> &[OutputConfig] {
+        if self.outputs.is_empty() {
+            std::slice::from_ref(&self.output)
+        } else {
+            &self.outputs
+        }
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy.unwrap_or(if self.contiguous_hours.is_some() {
+            Strategy::Contiguous
+        } else {
+            Strategy::Limits
+        })
+    }
+
+    pub fn is_forced_on<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        self.forced_on.iter().any(|range| range.contains(time))
+    }
+
+    /// Whether the switch may be on at `time` based on `allowed_hours` and `forced_off`
+    pub fn is_allowed<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        !self.forced_off.iter().any(|range| range.contains(time))
+            && (self.allowed_hours.is_empty()
+                || self.allowed_hours.iter().any(|range| range.contains(time)))
+    }
+}
+
+/// One switch and how it is controlled
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutputConfig {
+    /// GPIO pin to control. Not needed for network relays.
+    pub pin: Option<u8>,
+
+    /// How the switch is controlled. Defaults to the GPIO `pin` on `/dev/gpiochip0`.
+    #[serde(default)]
+    pub actuator: ActuatorConfig,
+}
+
+impl fmt::Display for OutputConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.actuator {
+            ActuatorConfig::Gpio(_) => match self.pin {
+                Some(pin) => write!(f, "GPIO {}", pin),
+                None => write!(f, "GPIO"),
+            },
+            ActuatorConfig::Shelly { url, switch_id } => {
+                write!(f, "Shelly {} ({})", url, switch_id)
+            }
+            ActuatorConfig::Tasmota { url, relay: None } => write!(f, "Tasmota {}", url),
+            ActuatorConfig::Tasmota {
+                url,
+                relay: Some(relay),
+            } => write!(f, "Tasmota {} ({})", url, relay),
+        }
+    }
+}
+
+/// Device that turns the switch on and off
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ActuatorConfig {
+    /// Local GPIO pin
+    Gpio(GpioLineConfig),
+
+    /// Shelly Gen2 relay controlled with the RPC API, e.g. `http://192.168.1.10`
+    Shelly {
+        url: String,
+        #[serde(default)]
+        switch_id: u8,
+    },
+
+    /// Tasmota relay controlled with `cmnd` URLs. `relay` is the number of the relay on devices
+    /// with more than one.
+    Tasmota { url: String, relay: Option<u8> },
+}
+
+impl Default for ActuatorConfig {
+    fn default() -> Self {
+        ActuatorConfig::Gpio(GpioLineConfig::default())
+    }
+}
+
+/// Settings for requesting a GPIO line
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GpioLineConfig {
+    /// GPIO chip device, `/dev/gpiochip0` by default. E.g. `/dev/gpiochip4` on Raspberry Pi 5.
+    pub chip: Option<String>,
+
+    /// The switch is on when the pin is low, as on many relay boards
+    #[serde(default)]
+    pub active_low: bool,
+
+    /// Pull-up or pull-down resistor of the pin. Requires Linux 5.5 or later.
+    pub bias: Option<Bias>,
+
+    /// How the pin is driven, push-pull by default
+    pub drive: Option<Drive>,
+}
+
+impl GpioLineConfig {
+    pub fn chip(&self) -> &str {
+        self.chip.as_deref().unwrap_or("/dev/gpiochip0")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Bias {
+    Disabled,
+    PullUp,
+    PullDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Drive {
+    PushPull,
+    OpenDrain,
+    OpenSource,
+}
+
+/// Price limit, either in c/kWh or relative to the prices of the day
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Limit {
+    Absolute(f64),
+    Relative(RelativeLimit),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelativeLimit {
+    /// Price at this percentile of the day's prices, e.g. 25 for the cheapest quarter
+    Percentile(f64),
+
+    /// Average price of the day changed by this percentage, e.g. -20 for 20 % under average
+    AveragePercent(f64),
+}
+
+impl From<f64> for Limit {
+    fn from(limit: f64) -> Self {
+        Limit::Absolute(limit)
+    }
+}
+
+impl Limit {
+    /// Returns the limit in c/kWh for the given prices
+    pub fn resolve(&self, prices: &[f64]) -> f64 {
+        match self {
+            Limit::Absolute(limit) => *limit,
+            Limit::Relative(_) if prices.is_empty() => 0.0,
+            Limit::Relative(RelativeLimit::Percentile(percentile)) => {
+                let mut prices = prices.to_vec();
+                prices.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                // Interpolate linearly between the closest ranks
+                let rank = percentile.clamp(0.0, 100.0) / 100.0 * (prices.len() - 1) as f64;
+                let lower = prices[rank.floor() as usize];
+                let upper = prices[rank.ceil() as usize];
+                lower + (upper - lower) * rank.fract()
+            }
+            Limit::Relative(RelativeLimit::AveragePercent(percent)) => {
+                let avg = prices.iter().sum::<f64>() / prices.len() as f64;
+                // Use absolute value so that negative percentages are always under average
+                avg + avg.abs() * percent / 100.0
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Use `low_limit`, `high_limit`, `min_on_hours` and `max_on_hours`
+    Limits,
+    /// One uninterrupted run of `contiguous_hours`
+    Contiguous,
+    /// The cheapest `min_on_hours`, ignoring the price limits
+    Cheapest,
+    /// Whenever the price is at or under `low_limit`, ignoring the on-hour settings
+    Threshold,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Period {
+    #[serde(flatten)]
+    pub range: TimeRange,
+
+    /// Minimum duration to keep the switch on during the period if price is under `high_limit`
+    pub min_on_hours: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeRange {
+    /// Start time, e.g. "16:00"
+    pub start: NaiveTime,
+
+    /// End time, exclusive. If not after `start`, the range continues over midnight, so
+    /// "00:00" means the end of the day.
+    pub end: NaiveTime,
+
+    /// Days on which the range applies
+    #[serde(default)]
+    pub days: Days,
+}
+
+impl TimeRange {
+    pub fn contains<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        let time_of_day = time.time();
+        let in_range = if self.start < self.end {
+            self.start <= time_of_day && time_of_day < self.end
+        } else {
+            self.start <= time_of_day || time_of_day < self.end
+        };
+        in_range && self.days.contains(time.weekday())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Days {
+    #[default]
+    All,
+    Weekdays,
+    Weekends,
+}
+
+impl Days {
+    pub fn contains(&self, weekday: Weekday) -> bool {
+        let weekend = matches!(weekday, Weekday::Sat | Weekday::Sun);
+        match self {
+            Days::All => true,
+            Days::Weekdays => !weekend,
+            Days::Weekends => weekend,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,15 +381,329 @@ pub struct EmailConfig {
     pub to: Vec<String>,
 }
 
+/// API to get the spot prices from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum PriceSourceKind {
+    /// ENTSO-E Transparency Platform, with the security token of its API
+    Entsoe {
+        token: String,
+    },
+    Elering,
+    Porssisahko,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceSourceConfig {
+    #[serde(flatten)]
+    pub source: PriceSourceKind,
+
+    /// Request timeout in seconds
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+impl From<PriceSourceKind> for PriceSourceConfig {
+    fn from(source: PriceSourceKind) -> Self {
+        Self {
+            source,
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+}
+
+/// Costs added to the spot price, all in c/kWh including VAT
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tariff {
+    /// Retailer margin
+    #[serde(default)]
+    pub margin: f64,
+
+    /// Electricity tax
+    #[serde(default)]
+    pub electricity_tax: f64,
+
+    /// Transfer fees by time of day. The first matching fee is used.
+    #[serde(default)]
+    pub transfer: Vec<TransferFee>,
+
+    /// Transfer fees for some months of the year, used instead of `transfer`
+    #[serde(default)]
+    pub seasons: Vec<Season>,
+}
+
+impl Tariff {
+    /// Total of the costs added to the spot price at `time`
+    pub fn costs_at<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> f64 {
+        let transfer = self
+            .seasons
+            .iter()
+            .find(|season| season.months.contains(&time.month()))
+            .map_or(&self.transfer, |season| &season.transfer)
+            .iter()
+            .find(|fee| fee.hours.is_empty() || fee.hours.iter().any(|range| range.contains(time)))
+            .map_or(0.0, |fee| fee.price);
+        self.margin + self.electricity_tax + transfer
+    }
+
+    /// Returns the prices with the costs added
+    pub fn apply(&self, prices: &[Price]) -> Vec<Price> {
+        prices
+            .iter()
+            .map(|price| Price {
+                validity: price.validity,
+                price: round_price(price.price + self.costs_at(&price.validity)),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferFee {
+    /// Transfer fee
+    pub price: f64,
+
+    /// Times when this fee applies. If empty, the fee applies at any time.
+    #[serde(default)]
+    pub hours: Vec<TimeRange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Season {
+    /// Months of the season, 1-12
+    pub months: Vec<u32>,
+
+    /// Transfer fees during the season
+    pub transfer: Vec<TransferFee>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub schedules: Vec<ScheduleConfig>,
     pub email: Option<EmailConfig>,
+    pub tariff: Option<Tariff>,
+
+    /// Bidding zone to get the prices for
+    #[serde(default)]
+    pub price_area: PriceArea,
+
+    /// VAT to add to the spot prices. Defaults to the VAT of `price_area` at the time.
+    pub vat_percent: Option<f64>,
+
+    /// Sources to get the prices from, in order of preference
+    #[serde(default)]
+    pub price_sources: Vec<PriceSourceConfig>,
+
+    /// Cross-checks the prices with a second source, allowing this difference in c/kWh
+    pub price_tolerance: Option<f64>,
+
+    /// File to simulate the GPIO chip with instead of `/dev/gpiochip0`, for development
+    pub simulated_gpio: Option<PathBuf>,
+
+    /// Pause between turning on switches, to avoid inrush current spikes
+    pub stagger_secs: Option<f64>,
+
+    /// Maximum total power in kW of the devices that may be on at the same time
+    pub max_load_kw: Option<f64>,
 }
 
 impl Config {
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let file = File::open(path).wrap_err("Failed to open config.json")?;
-        serde_json::from_reader(file).wrap_err("Failed to parse config.json")
+        let config: Self = serde_json::from_reader(file).wrap_err("Failed to parse config.json")?;
+        config.validate().wrap_err("Invalid config.json")?;
+        Ok(config)
+    }
+
+    /// Checks the settings that depend on each other
+    pub fn validate(&self) -> Result<()> {
+        for schedule in &self.schedules {
+            schedule.validate()?;
+        }
+        if let Some(stagger_secs) = self.stagger_secs {
+            Duration::try_from_secs_f64(stagger_secs)
+                .wrap_err_with(|| format!("Invalid stagger_secs {}", stagger_secs))?;
+        }
+        if let Some(max_load_kw) = self.max_load_kw {
+            if max_load_kw <= 0.0 {
+                return Err(eyre!("max_load_kw must be positive"));
+            }
+            for schedule in &self.schedules {
+                match schedule.power_kw {
+                    None => {
+                        return Err(eyre!(
+                            "{} needs power_kw when max_load_kw is set",
+                            schedule.name
+                        ))
+                    }
+                    Some(power_kw) if power_kw > max_load_kw => {
+                        return Err(eyre!(
+                            "Power of {} ({} kW) exceeds max_load_kw ({} kW)",
+                            schedule.name,
+                            power_kw,
+                            max_load_kw
+                        ))
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Pause between turning on switches. Invalid values are rejected by `validate`.
+    pub fn stagger(&self) -> Duration {
+        self.stagger_secs
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .unwrap_or_default()
+    }
+
+    /// Configured price sources, or Elering and porssisahko.net
+    pub fn price_sources(&self) -> Vec<PriceSourceConfig> {
+        if !self.price_sources.is_empty() {
+            return self.price_sources.clone();
+        }
+        [PriceSourceKind::Elering, PriceSourceKind::Porssisahko]
+            .into_iter()
+            .map(PriceSourceConfig::from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, OutputConfig, ScheduleConfig};
+
+    fn config(max_load_kw: f64, power_kw: Option<f64>) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "schedules": [
+                { "name": "Boiler", "pin": 17, "min_on_hours": 2, "max_on_hours": 2, "power_kw": 3.0 },
+                { "name": "Heater", "pin": 27, "min_on_hours": 2, "max_on_hours": 2, "power_kw": power_kw }
+            ],
+            "email": null,
+            "tariff": null,
+            "max_load_kw": max_load_kw
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate_max_load() {
+        assert!(config(6.0, Some(2.0)).validate().is_ok());
+        assert!(config(6.0, None).validate().is_err());
+        assert!(config(2.5, Some(2.0)).validate().is_err());
+        assert!(config(0.0, Some(2.0)).validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_stagger() {
+        let mut config = config(6.0, Some(2.0));
+        config.stagger_secs = Some(0.5);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.stagger().as_millis(), 500);
+        for invalid in [-1.0, 1e300, f64::NAN] {
+            config.stagger_secs = Some(invalid);
+            assert!(config.validate().is_err());
+        }
+    }
+
+    fn schedule(schedule: serde_json::Value) -> ScheduleConfig {
+        let mut schedule_config = serde_json::json!({
+            "name": "Boiler",
+            "min_on_hours": 2,
+            "max_on_hours": 2
+        });
+        schedule_config
+            .as_object_mut()
+            .unwrap()
+            .extend(schedule.as_object().unwrap().clone());
+        serde_json::from_value(schedule_config).unwrap()
+    }
+
+    #[test]
+    fn test_validate_required_energy() {
+        let valid = schedule(serde_json::json!({
+            "pin": 17,
+            "required_kwh": 6.0,
+            "power_kw": 3.0
+        }));
+        assert!(valid.validate().is_ok());
+        let without_power = schedule(serde_json::json!({ "pin": 17, "required_kwh": 6.0 }));
+        assert!(without_power.validate().is_err());
+        let zero_power = schedule(serde_json::json!({
+            "pin": 17,
+            "required_kwh": 6.0,
+            "power_kw": 0.0
+        }));
+        assert!(zero_power.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_periods() {
+        let periods = serde_json::json!({
+            "pin": 17,
+            "periods": [
+                { "start": "00:00", "end": "12:00", "min_on_hours": 1 },
+                { "start": "12:00", "end": "00:00", "min_on_hours": 1 }
+            ]
+        });
+        assert!(schedule(periods.clone()).validate().is_ok());
+        let mut too_many = schedule(periods);
+        too_many.max_on_hours = 1;
+        assert!(too_many.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_outputs() {
+        let outputs = serde_json::json!({ "outputs": [{ "pin": 17 }, { "pin": 27 }] });
+        assert!(schedule(outputs).validate().is_ok());
+        let both = serde_json::json!({ "pin": 22, "outputs": [{ "pin": 17 }, { "pin": 27 }] });
+        assert!(schedule(both).validate().is_err());
+        let pin_zero = serde_json::json!({ "pin": 0, "outputs": [{ "pin": 17 }, { "pin": 27 }] });
+        assert!(schedule(pin_zero).validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_pin() {
+        assert!(schedule(serde_json::json!({ "pin": 0 })).validate().is_ok());
+        assert!(schedule(serde_json::json!({})).validate().is_err());
+        let without_pin = serde_json::json!({ "outputs": [{ "pin": 17 }, {}] });
+        assert!(schedule(without_pin).validate().is_err());
+        let relay = serde_json::json!({
+            "actuator": { "type": "shelly", "url": "http://192.168.1.10" }
+        });
+        assert!(schedule(relay).validate().is_ok());
+    }
+
+    #[test]
+    fn test_output_display() {
+        let output = |json| {
+            serde_json::from_value::<OutputConfig>(json)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(output(serde_json::json!({ "pin": 17 })), "GPIO 17");
+        assert_eq!(
+            output(serde_json::json!({
+                "actuator": { "type": "shelly", "url": "http://192.168.1.10" }
+            })),
+            "Shelly http://192.168.1.10 (0)"
+        );
+        assert_eq!(
+            output(serde_json::json!({
+                "actuator": { "type": "tasmota", "url": "http://192.168.1.11" }
+            })),
+            "Tasmota http://192.168.1.11"
+        );
+        assert_eq!(
+            output(serde_json::json!({
+                "actuator": { "type": "tasmota", "url": "http://192.168.1.11", "relay": 2 }
+            })),
+            "Tasmota http://192.168.1.11 (2)"
+        );
     }
 }
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Days, Duration, Local, NaiveDate, Timelike, Utc};
+use chrono::{DateTime, Days, Duration, Local, NaiveDate, NaiveTime, Utc};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RelativeDate {
@@ -7,24 +7,6 @@ pub enum RelativeDate {
 }
 
 impl RelativeDate {
-    pub fn to_start_and_end(&self) -> (DateTime<Utc>, DateTime<Utc>) {
-        let start = Local::now()
-            .with_hour(0)
-            .unwrap()
-            .with_minute(0)
-            .unwrap()
-            .with_second(0)
-            .unwrap()
-            .with_nanosecond(0)
-            .unwrap()
-            + Days::new(match self {
-                RelativeDate::Today => 0,
-                RelativeDate::Tomorrow => 1,
-            });
-        let end = start + Days::new(1) - Duration::seconds(1);
-        (start.with_timezone(&Utc), end.with_timezone(&Utc))
-    }
-
     pub fn to_naive_date(&self) -> NaiveDate {
         match self {
             RelativeDate::Today => Local::now(),
@@ -34,3 +16,14 @@ impl RelativeDate {
         .date()
     }
 }
+
+/// Returns the first and the last second of `date` in local time
+pub fn start_and_end_of_day(date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = date
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .unwrap();
+    let end = start + Days::new(1) - Duration::seconds(1);
+    (start.with_timezone(&Utc), end.with_timezone(&Utc))
+}
@@ -1,35 +1,63 @@
-use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Timelike};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
 use eyre::{Report, Result};
 use lettre::message::header::ContentType;
 use lettre::transport::smtp::authentication::Credentials;
 use lettre::{Message, SmtpTransport, Transport};
 
 use crate::config::EmailConfig;
+use crate::overrides::{Action, TimedOverride, TimedOverrides};
+use crate::prices::PriceMismatch;
 use crate::schedule::Schedule;
 
 pub struct EmailClient(Option<EmailConfig>);
 
+/// New state of one switch
+pub struct OutputStateChange<'a> {
+    /// Name of the schedule
+    pub name: &'a str,
+
+    /// Description of the switch, if the schedule has several
+    pub output: Option<String>,
+    pub on: bool,
+}
+
 impl EmailClient {
     pub fn new(config: &Option<EmailConfig>) -> Self {
         Self(config.clone())
     }
 
-    pub fn send_schedule(&self, date: NaiveDate, schedule: &Schedule) -> Result<()> {
+    /// Sends the schedule of `date` with the boosts and pauses applied
+    pub fn send_schedule(
+        &self,
+        date: NaiveDate,
+        schedule: &Schedule,
+        timed_overrides: &TimedOverrides,
+    ) -> Result<()> {
+        let schedule = schedule.with_timed_overrides(timed_overrides);
         let subject = format!("Aikataulu {}", date.format("%d.%m.%Y"));
         let mut body: Vec<String> = Vec::new();
+        let prices = schedule.effective_prices();
 
         for pin in &schedule.pins {
-            if pin.on_hours.is_empty() {
+            let on_slots = pin.effective_on_slots();
+            if on_slots.is_empty() {
                 body.push(format!("{}: ei päälläoloaikoja\n", pin.name));
             } else {
-                let ranges = to_ranges(&pin.on_hours);
+                let ranges = to_ranges(&on_slots, pin.slot_length());
+                let energy = match (pin.energy_kwh(), pin.cost_eur(&prices)) {
+                    (Some(kwh), Some(eur)) => {
+                        format!("Energia: {:.1} kWh, kustannus {:.2} €\n", kwh, eur)
+                    }
+                    _ => String::new(),
+                };
                 body.push(format!(
-                    "{}: {} ({} h)\nKeskihinta: päällä {:.3}, pois {:.3}\n",
+                    "{}: {} ({} h)\nKeskihinta: päällä {:.3}, pois {:.3}\n{}",
                     pin.name,
                     ranges,
-                    pin.on_hours.len(),
-                    pin.avg_price(&schedule.prices, true),
-                    pin.avg_price(&schedule.prices, false)
+                    pin.on_duration().num_minutes() as f64 / 60.0,
+                    pin.avg_price(&prices, true),
+                    pin.avg_price(&prices, false),
+                    energy
                 ));
             }
         }
@@ -37,18 +65,141 @@ impl EmailClient {
             "Vuorokauden keskihinta: {:.3}",
             schedule.avg_price()
         ));
+        if let (Some(kwh), Some(eur)) = (schedule.energy_kwh(), schedule.cost_eur()) {
+            body.push(format!("Yhteensä: {:.1} kWh, {:.2} €", kwh, eur));
+        }
+
+        self.send(subject, body.join("\n"))
+    }
+
+    /// Sends what changed when the schedule of `date` was recomputed, with the boosts and pauses
+    /// applied to both schedules
+    pub fn send_schedule_changes(
+        &self,
+        date: NaiveDate,
+        old: &Schedule,
+        new: &Schedule,
+        timed_overrides: &TimedOverrides,
+    ) -> Result<()> {
+        let old = &old.with_timed_overrides(timed_overrides);
+        let new = &new.with_timed_overrides(timed_overrides);
+        let subject = format!("Aikataulu {} laskettu uudelleen", date.format("%d.%m.%Y"));
+        let mut body: Vec<String> = Vec::new();
+
+        for pin in &new.pins {
+            let old_slots = old
+                .pins
+                .iter()
+                .find(|old_pin| old_pin.name == pin.name)
+                .map(|old_pin| old_pin.effective_on_slots())
+                .unwrap_or_default();
+            let new_slots = pin.effective_on_slots();
+            let added = new_slots
+                .iter()
+                .filter(|slot| !old_slots.contains(slot))
+                .copied()
+                .collect::<Vec<_>>();
+            let removed = old_slots
+                .iter()
+                .filter(|slot| !new_slots.contains(slot))
+                .copied()
+                .collect::<Vec<_>>();
+
+            let mut lines = vec![format!("{}:", pin.name)];
+            if !added.is_empty() {
+                lines.push(format!("Lisätty: {}", to_ranges(&added, pin.slot_length())));
+            }
+            if !removed.is_empty() {
+                lines.push(format!(
+                    "Poistettu: {}",
+                    to_ranges(&removed, pin.slot_length())
+                ));
+            }
+            if added.is_empty() && removed.is_empty() {
+                lines.push("Ei muutoksia".to_string());
+            } else {
+                lines.push(format!(
+                    "Nyt: {} ({} h)",
+                    to_ranges(&new_slots, pin.slot_length()),
+                    pin.on_duration().num_minutes() as f64 / 60.0
+                ));
+            }
+            body.push(format!("{}\n", lines.join("\n")));
+        }
+        for old_pin in &old.pins {
+            if !new.pins.iter().any(|pin| pin.name == old_pin.name) {
+                body.push(format!("{}: poistettu\n", old_pin.name));
+            }
+        }
 
         self.send(subject, body.join("\n"))
     }
 
-    pub fn send_pin_state_change(&self, pins: &[(&str, bool)], powered_on: bool) -> Result<()> {
+    /// Sends the changed switches, and the schedules that are kept off to stay within
+    /// `max_load_kw`
+    pub fn send_pin_state_change(
+        &self,
+        changes: &[OutputStateChange],
+        kept_off: &[&str],
+        powered_on: bool,
+    ) -> Result<()> {
         let subject = format!(
             "Tilamuutos{}",
             if powered_on { " (virta kytketty)" } else { "" }
         );
-        let body = pins
+        self.send(subject, state_change_body(changes, kept_off))
+    }
+
+    pub fn send_price_mismatch(&self, date: NaiveDate, mismatch: &PriceMismatch) -> Result<()> {
+        let subject = format!("Hinnat poikkeavat {}", date.format("%d.%m.%Y"));
+        let (a, b) = mismatch.sources;
+        let mut body = vec![format!(
+            "Lähteiden {} ja {} hinnat poikkeavat toisistaan. Aikataulu lasketaan lähteen {} hinnoilla.\n",
+            a, b, a
+        )];
+        body.extend(mismatch.differences.iter().map(|difference| {
+            format!(
+                "{}: {:.3} / {:.3}",
+                difference.hour.format("%H:%M"),
+                difference.prices.0,
+                difference.prices.1
+            )
+        }));
+        self.send(subject, body.join("\n"))
+    }
+
+    pub fn send_timed_override_started(&self, timed_override: &TimedOverride) -> Result<()> {
+        let subject = format!(
+            "{}: {}",
+            action_name(timed_override.action),
+            timed_override.name
+        );
+        let body = format!(
+            "{} {} {} asti ({})",
+            timed_override.name,
+            if timed_override.on() {
+                "päällä"
+            } else {
+                "pois"
+            },
+            timed_override.end.format("%d.%m.%Y %H:%M"),
+            timed_override.author
+        );
+        self.send(subject, body)
+    }
+
+    /// Sends a notification of boosts and pauses that have expired or were cancelled
+    pub fn send_timed_overrides_ended(&self, timed_overrides: &[TimedOverride]) -> Result<()> {
+        let subject = "Tehostus tai tauko päättyi".to_string();
+        let body = timed_overrides
             .iter()
-            .map(|(name, state)| format!("{}: {}", name, if *state { "päällä" } else { "pois" }))
+            .map(|o| {
+                format!(
+                    "{}: {} päättyi, noudatetaan aikataulua",
+                    o.name,
+                    action_name(o.action).to_lowercase()
+                )
+            })
             .collect::<Vec<_>>()
             .join("\n");
         self.send(subject, body)
@@ -95,28 +246,96 @@ impl EmailClient {
     }
 }
 
-/// Assumes that hours is ordered
-fn to_ranges(hours: &[DateTime<FixedOffset>]) -> String {
-    if hours.is_empty() {
+fn action_name(action: Action) -> &'static str {
+    match action {
+        Action::Boost => "Tehostus",
+        Action::Pause => "Tauko",
+    }
+}
+
+/// One line per switch, with the switch in parentheses if the schedule has several
+fn state_change_body(changes: &[OutputStateChange], kept_off: &[&str]) -> String {
+    let mut body = changes
+        .iter()
+        .map(|change| {
+            let state = if change.on { "päällä" } else { "pois" };
+            match &change.output {
+                Some(output) => format!("{} ({}): {}", change.name, output, state),
+                None => format!("{}: {}", change.name, state),
+            }
+        })
+        .collect::<Vec<_>>();
+    if !kept_off.is_empty() {
+        body.push(format!(
+            "\nPidetään pois päältä kuormarajan vuoksi: {}",
+            kept_off.join(", ")
+        ));
+    }
+    body.join("\n")
+}
+
+/// Assumes that slots is ordered
+fn to_ranges(slots: &[DateTime<FixedOffset>], slot_length: Duration) -> String {
+    if slots.is_empty() {
         return String::new();
     }
 
+    let format_range = |start: DateTime<FixedOffset>, end: DateTime<FixedOffset>| {
+        // Show the last minute of the range, e.g. 00:00-00:59
+        let last_minute = end + slot_length - Duration::minutes(1);
+        format!("{}-{}", start.format("%H:%M"), last_minute.format("%H:%M"))
+    };
+
     let mut ranges = Vec::new();
 
-    let mut start = hours[0];
-    let mut end = hours[0];
+    let mut start = slots[0];
+    let mut end = slots[0];
 
-    for entry in hours.iter().skip(1) {
-        let hour = *entry;
-        if hour == end + Duration::hours(1) {
-            end = hour;
+    for entry in slots.iter().skip(1) {
+        let slot = *entry;
+        if slot == end + slot_length {
+            end = slot;
         } else {
-            ranges.push(format!("{:02}:00-{:02}:59", start.hour(), end.hour()));
-            start = hour;
-            end = hour;
+            ranges.push(format_range(start, end));
+            start = slot;
+            end = slot;
         }
     }
 
-    ranges.push(format!("{:02}:00-{:02}:59", start.hour(), end.hour()));
+    ranges.push(format_range(start, end));
     ranges.join(", ")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{state_change_body, OutputStateChange};
+
+    #[test]
+    fn test_state_change_body() {
+        let changes = [
+            OutputStateChange {
+                name: "Boiler",
+                output: None,
+                on: true,
+            },
+            OutputStateChange {
+                name: "Heater",
+                output: Some("GPIO 27".to_string()),
+                on: false,
+            },
+            OutputStateChange {
+                name: "Heater",
+                output: Some("Shelly http://192.168.1.10 (1)".to_string()),
+                on: false,
+            },
+        ];
+        assert_eq!(
+            state_change_body(&changes, &[]),
+            "Boiler: päällä\nHeater (GPIO 27): pois\nHeater (Shelly http://192.168.1.10 (1)): pois"
+        );
+        assert_eq!(
+            state_change_body(&changes[..1], &["Sauna", "Heater"]),
+            "Boiler: päällä\n\nPidetään pois päältä kuormarajan vuoksi: Sauna, Heater"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 pub mod config;
 pub mod domain;
 pub mod email;
+pub mod overrides;
+pub mod paths;
 pub mod prices;
 pub mod schedule;
@@ -0,0 +1,336 @@
+use chrono::{DateTime, Days, Duration, FixedOffset, NaiveTime, TimeZone};
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Temporary manual control of a pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Keep on regardless of the schedule
+    Boost,
+
+    /// Keep off regardless of the schedule
+    Pause,
+}
+
+/// Boost or pause of one schedule for a limited time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimedOverride {
+    /// Name of the schedule
+    pub name: String,
+    pub action: Action,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+
+    /// Who made the change
+    pub author: String,
+}
+
+impl TimedOverride {
+    pub fn on(&self) -> bool {
+        self.action == Action::Boost
+    }
+
+    pub fn is_active<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> bool {
+        self.start <= *now && *now < self.end
+    }
+
+    /// Whether the override is in effect for some of the slot starting at `slot`
+    pub fn covers<Tz: TimeZone>(&self, slot: &DateTime<Tz>, length: Duration) -> bool {
+        *slot < self.end && self.start < slot.clone() + length
+    }
+}
+
+/// Boosts and pauses that are in effect or about to expire, saved in `overrides.json`
+#[derive(Debug, Default)]
+pub struct TimedOverrides {
+    path: PathBuf,
+    pub overrides: Vec<TimedOverride>,
+}
+
+impl TimedOverrides {
+    pub fn load() -> Result<Self> {
+        Self::load_from(OVERRIDES_FILE_NAME)
+    }
+
+    /// Loads the overrides, or returns no overrides if the file doesn't exist
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let overrides = match File::open(&path) {
+            Ok(file) => serde_json::from_reader(file)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Self { path, overrides })
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        write!(
+            File::create(&self.path)?,
+            "{}",
+            serde_json::to_string_pretty(&self.overrides)?
+        )
+    }
+
+    /// Adds an override, replacing any earlier override of the same schedule
+    pub fn add(&mut self, timed_override: TimedOverride) {
+        self.overrides.retain(|o| o.name != timed_override.name);
+        self.overrides.push(timed_override);
+    }
+
+    /// Removes the override of the schedule `name`
+    pub fn cancel(&mut self, name: &str) -> Option<TimedOverride> {
+        let index = self.overrides.iter().position(|o| o.name == name)?;
+        Some(self.overrides.remove(index))
+    }
+
+    /// Returns the active override of the schedule `name`, if any
+    pub fn active<Tz: TimeZone>(&self, name: &str, now: &DateTime<Tz>) -> Option<&TimedOverride> {
+        self.overrides
+            .iter()
+            .find(|o| o.name == name && o.is_active(now))
+    }
+
+    /// Returns the override of the schedule `name` that is in effect for some of the slot starting
+    /// at `slot`, if any
+    pub fn covering<Tz: TimeZone>(
+        &self,
+        name: &str,
+        slot: &DateTime<Tz>,
+        length: Duration,
+    ) -> Option<&TimedOverride> {
+        self.overrides
+            .iter()
+            .find(|o| o.name == name && o.covers(slot, length))
+    }
+
+    /// Removes and returns the overrides that have ended by `now`
+    pub fn remove_expired<Tz: TimeZone>(&mut self, now: &DateTime<Tz>) -> Vec<TimedOverride> {
+        let (expired, overrides) = self.overrides.drain(..).partition(|o| o.end <= *now);
+        self.overrides = overrides;
+        expired
+    }
+}
+
+pub const OVERRIDES_FILE_NAME: &str = "overrides.json";
+
+/// End of an override starting at `now`, lasting for `hours` or until the next `until` time of
+/// day. A time of day skipped by a change to summer time is moved an hour later.
+pub fn end_time<Tz: TimeZone>(
+    now: &DateTime<Tz>,
+    hours: Option<f64>,
+    until: Option<NaiveTime>,
+) -> Result<DateTime<Tz>> {
+    match (hours, until) {
+        (Some(hours), None) if hours > 0.0 => {
+            let minutes = (hours * 60.0).round();
+            (minutes < Duration::max_value().num_minutes() as f64)
+                .then(|| Duration::minutes(minutes as i64))
+                .and_then(|duration| now.clone().checked_add_signed(duration))
+                .ok_or_else(|| eyre!("Too many hours: {}", hours))
+        }
+        (None, Some(until)) => {
+            let today = now.date_naive();
+            let date = if until > now.time() {
+                today
+            } else {
+                today + Days::new(1)
+            };
+            let end = date.and_time(until);
+            end.and_local_timezone(now.timezone())
+                .earliest()
+                .or_else(|| {
+                    (end + Duration::hours(1))
+                        .and_local_timezone(now.timezone())
+                        .earliest()
+                })
+                .ok_or_else(|| eyre!("Invalid time {}", until))
+        }
+        _ => Err(eyre!(
+            "Give either a positive number of hours or an end time"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{end_time, Action, TimedOverride, TimedOverrides};
+    use chrono::{
+        DateTime, Duration, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+    };
+
+    fn dt(text: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(text).unwrap()
+    }
+
+    fn boost(name: &str, start: &str, end: &str) -> TimedOverride {
+        TimedOverride {
+            name: name.to_string(),
+            action: Action::Boost,
+            start: dt(start),
+            end: dt(end),
+            author: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_active_and_expired() {
+        let mut overrides = TimedOverrides::default();
+        overrides.add(boost(
+            "Boiler",
+            "2024-01-15T10:00:00+02:00",
+            "2024-01-15T12:00:00+02:00",
+        ));
+        overrides.add(boost(
+            "Heater",
+            "2024-01-15T10:00:00+02:00",
+            "2024-01-15T11:00:00+02:00",
+        ));
+
+        let now = dt("2024-01-15T10:30:00+02:00");
+        assert!(overrides.active("Boiler", &now).unwrap().on());
+        assert!(overrides.active("Sauna", &now).is_none());
+        assert!(overrides
+            .active("Boiler", &(now - Duration::hours(1)))
+            .is_none());
+
+        let expired = overrides.remove_expired(&dt("2024-01-15T11:00:00+02:00"));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].name, "Heater");
+        assert_eq!(overrides.overrides.len(), 1);
+    }
+
+    #[test]
+    fn test_add_replaces() {
+        let mut overrides = TimedOverrides::default();
+        overrides.add(boost(
+            "Boiler",
+            "2024-01-15T10:00:00+02:00",
+            "2024-01-15T12:00:00+02:00",
+        ));
+        overrides.add(TimedOverride {
+            action: Action::Pause,
+            ..boost(
+                "Boiler",
+                "2024-01-15T11:00:00+02:00",
+                "2024-01-15T18:00:00+02:00",
+            )
+        });
+        assert_eq!(overrides.overrides.len(), 1);
+        assert!(!overrides.overrides[0].on());
+        assert!(overrides.cancel("Boiler").is_some());
+        assert!(overrides.overrides.is_empty());
+    }
+
+    /// Finnish time in 2024, with summer time from March 31 to October 27
+    #[derive(Debug, Clone, Copy)]
+    struct Helsinki;
+
+    impl TimeZone for Helsinki {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Helsinki
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            // Summer time first, so that it is the earliest of ambiguous times
+            let valid = [3, 2]
+                .map(|hours| FixedOffset::east_opt(hours * 3600).unwrap())
+                .into_iter()
+                .filter(|offset| {
+                    let utc = *local - Duration::seconds(offset.local_minus_utc().into());
+                    self.offset_from_utc_datetime(&utc) == *offset
+                })
+                .collect::<Vec<_>>();
+            match valid[..] {
+                [] => LocalResult::None,
+                [offset] => LocalResult::Single(offset),
+                [earliest, latest, ..] => LocalResult::Ambiguous(earliest, latest),
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let utc = utc.and_utc();
+            let summer = dt("2024-03-31T01:00:00Z") <= utc && utc < dt("2024-10-27T01:00:00Z");
+            FixedOffset::east_opt(if summer { 3 } else { 2 } * 3600).unwrap()
+        }
+    }
+
+    fn helsinki(text: &str) -> DateTime<Helsinki> {
+        dt(text).with_timezone(&Helsinki)
+    }
+
+    fn time(text: &str) -> Option<NaiveTime> {
+        Some(text.parse().unwrap())
+    }
+
+    #[test]
+    fn test_end_time() {
+        let now = helsinki("2024-01-15T10:00:00+02:00");
+        let end = |hours, until| end_time(&now, hours, until).map(|end| end.fixed_offset());
+        assert_eq!(
+            end(Some(1.5), None).unwrap(),
+            dt("2024-01-15T11:30:00+02:00")
+        );
+        assert_eq!(
+            end(None, time("22:00")).unwrap(),
+            dt("2024-01-15T22:00:00+02:00")
+        );
+        // Times of day that have passed are tomorrow
+        assert_eq!(
+            end(None, time("06:00")).unwrap(),
+            dt("2024-01-16T06:00:00+02:00")
+        );
+        assert_eq!(
+            end(None, time("10:00")).unwrap(),
+            dt("2024-01-16T10:00:00+02:00")
+        );
+
+        assert!(end(Some(1e30), None).is_err());
+        assert!(end(Some(f64::INFINITY), None).is_err());
+        assert!(end(Some(f64::NAN), None).is_err());
+        assert!(end(Some(0.0), None).is_err());
+        assert!(end(None, None).is_err());
+        assert!(end(Some(1.0), time("22:00")).is_err());
+    }
+
+    #[test]
+    fn test_end_time_dst() {
+        let end = |now, hours, until| {
+            end_time(&helsinki(now), hours, until)
+                .unwrap()
+                .fixed_offset()
+        };
+        // Hours are real hours, even if the clock is turned forward
+        assert_eq!(
+            end("2024-03-31T02:00:00+02:00", Some(2.0), None),
+            dt("2024-03-31T05:00:00+03:00")
+        );
+        // 03:30 is skipped when the clock is turned forward from 03:00 to 04:00
+        assert_eq!(
+            end("2024-03-30T23:00:00+02:00", None, time("03:30")),
+            dt("2024-03-31T04:30:00+03:00")
+        );
+        // 03:30 happens twice when the clock is turned back from 04:00 to 03:00
+        assert_eq!(
+            end("2024-10-26T23:00:00+03:00", None, time("03:30")),
+            dt("2024-10-27T03:30:00+03:00")
+        );
+        assert_eq!(
+            end("2024-10-26T23:00:00+03:00", None, time("05:00")),
+            dt("2024-10-27T05:00:00+02:00")
+        );
+    }
+}
@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+use crate::overrides::OVERRIDES_FILE_NAME;
+use crate::prices::{PriceStore, PRICE_DIR_NAME};
+use crate::schedule::SCHEDULE_DIR_NAME;
+
+/// Where the schedules, boosts and pauses, and fetched prices are kept
+#[derive(Debug, Clone)]
+pub struct Paths {
+    pub schedules: PathBuf,
+    pub overrides: PathBuf,
+    pub prices: PathBuf,
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        Self {
+            schedules: PathBuf::from(SCHEDULE_DIR_NAME),
+            overrides: PathBuf::from(OVERRIDES_FILE_NAME),
+            prices: PathBuf::from(PRICE_DIR_NAME),
+        }
+    }
+}
+
+impl Paths {
+    pub fn price_store(&self) -> PriceStore {
+        PriceStore::new(&self.prices)
+    }
+}
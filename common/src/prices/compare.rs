@@ -0,0 +1,124 @@
+use chrono::{DateTime, FixedOffset, Timelike};
+
+use crate::prices::Price;
+
+/// Hours where the prices of two sources differ more than the tolerance
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceMismatch {
+    pub sources: (&'static str, &'static str),
+    pub differences: Vec<PriceDifference>,
+}
+
+/// Average prices of one hour from two sources
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceDifference {
+    pub hour: DateTime<FixedOffset>,
+    pub prices: (f64, f64),
+}
+
+/// Compares hourly average prices, so that sources with different resolutions can be compared.
+/// Hours missing from either series are reported with `NaN` as the missing price.
+pub fn compare_prices(a: &[Price], b: &[Price], tolerance: f64) -> Vec<PriceDifference> {
+    let a = hourly_averages(a);
+    let b = hourly_averages(b);
+    let mut hours = a
+        .iter()
+        .chain(&b)
+        .map(|(hour, _)| *hour)
+        .collect::<Vec<_>>();
+    hours.sort();
+    hours.dedup();
+
+    let price_at = |prices: &[(DateTime<FixedOffset>, f64)], hour| {
+        prices
+            .iter()
+            .find(|(h, _)| *h == hour)
+            .map_or(f64::NAN, |(_, price)| *price)
+    };
+    hours
+        .into_iter()
+        .map(|hour| PriceDifference {
+            hour,
+            prices: (price_at(&a, hour), price_at(&b, hour)),
+        })
+        .filter(|difference| {
+            let (a, b) = difference.prices;
+            a.is_nan() || b.is_nan() || (a - b).abs() > tolerance
+        })
+        .collect()
+}
+
+fn hourly_averages(prices: &[Price]) -> Vec<(DateTime<FixedOffset>, f64)> {
+    let mut hours: Vec<(DateTime<FixedOffset>, f64, usize)> = Vec::new();
+    for price in prices {
+        let hour = price
+            .validity
+            .with_minute(0)
+            .and_then(|t| t.with_second(0))
+            .unwrap();
+        match hours.last_mut() {
+            Some((h, sum, count)) if *h == hour => {
+                *sum += price.price;
+                *count += 1;
+            }
+            _ => hours.push((hour, price.price, 1)),
+        }
+    }
+    hours
+        .into_iter()
+        .map(|(hour, sum, count)| (hour, sum / count as f64))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compare_prices;
+    use crate::prices::Price;
+    use chrono::{DateTime, Duration};
+
+    fn make_prices(slot_minutes: i64, values: &[f64]) -> Vec<Price> {
+        let start = DateTime::parse_from_rfc3339("2024-01-15T00:00:00+02:00").unwrap();
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, price)| Price {
+                validity: start + Duration::minutes(i as i64 * slot_minutes),
+                price: *price,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_within_tolerance() {
+        let a = make_prices(60, &[1.0, 2.0, 3.0]);
+        let b = make_prices(60, &[1.05, 1.95, 3.0]);
+        assert!(compare_prices(&a, &b, 0.1).is_empty());
+    }
+
+    #[test]
+    fn test_shifted_by_an_hour() {
+        let a = make_prices(60, &[1.0, 2.0, 3.0]);
+        let b = make_prices(60, &[2.0, 3.0, 3.0]);
+        let differences = compare_prices(&a, &b, 0.1);
+        assert_eq!(differences.len(), 2);
+        assert_eq!(differences[0].hour, a[0].validity);
+        assert_eq!(differences[0].prices, (1.0, 2.0));
+        assert_eq!(differences[1].prices, (2.0, 3.0));
+    }
+
+    #[test]
+    fn test_different_resolutions() {
+        let a = make_prices(60, &[1.0, 2.0]);
+        let b = make_prices(15, &[0.5, 1.5, 0.5, 1.5, 2.0, 2.0, 2.0, 2.0]);
+        assert!(compare_prices(&a, &b, 0.01).is_empty());
+    }
+
+    #[test]
+    fn test_missing_hour() {
+        let a = make_prices(60, &[1.0, 2.0]);
+        let b = make_prices(60, &[1.0]);
+        let differences = compare_prices(&a, &b, 0.1);
+        assert_eq!(differences.len(), 1);
+        assert!(differences[0].prices.1.is_nan());
+    }
+}
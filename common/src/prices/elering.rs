@@ -1,72 +1,73 @@
-use crate::domain::RelativeDate;
-use crate::prices::{round_price, Price};
-use chrono::{Local, TimeZone};
+use crate::domain::start_and_end_of_day;
+use crate::prices::{c_per_kwh, Price, PriceArea, PriceSource};
+use async_trait::async_trait;
+use chrono::{Local, NaiveDate, TimeZone};
 use eyre::{eyre, Result, WrapErr};
 use serde::Deserialize;
 use std::collections::HashMap;
 
-pub struct EleringPriceClient(reqwest::blocking::Client);
+pub struct EleringPriceClient(reqwest::Client);
 
 impl EleringPriceClient {
-    pub fn new() -> Self {
+    pub fn new(timeout: std::time::Duration) -> Self {
         Self(
-            reqwest::blocking::ClientBuilder::new()
-                .timeout(std::time::Duration::from_secs(10))
+            reqwest::ClientBuilder::new()
+                .timeout(timeout)
                 .build()
                 .unwrap(),
         )
     }
+}
+
+#[async_trait]
+impl PriceSource for EleringPriceClient {
+    fn name(&self) -> &'static str {
+        "Elering"
+    }
 
-    pub fn get_prices_for_date(&self, date: RelativeDate) -> Result<Vec<Price>> {
-        let (start, end) = date.to_start_and_end();
+    async fn get_prices_for_date(&self, date: NaiveDate, area: PriceArea) -> Result<Vec<Price>> {
+        let (start, end) = start_and_end_of_day(date);
         let response = self
             .0
             .get("https://dashboard.elering.ee/api/nps/price")
             .query(&[("start", start.to_rfc3339())])
             .query(&[("end", end.to_rfc3339())])
             .send()
+            .await
             .wrap_err_with(|| "Unable to request spot prices")?
             .json::<EleringResponse>()
+            .await
             .wrap_err_with(|| "Unable to parse spot prices")?;
 
         if !response.success {
             return Err(eyre!("Elering API returned error"));
         }
 
-        Ok(response
+        let prices = response
             .data
+            .get(area_key(area))
+            .ok_or_else(|| eyre!("Elering API returned no prices for {:?}", area))?;
+        Ok(prices
             .iter()
-            .filter_map(
-                |(key, prices)| {
-                    if key == "fi" {
-                        Some(prices)
-                    } else {
-                        None
-                    }
-                },
-            )
-            .flat_map(|prices| prices.iter())
-            .map(|price| {
-                let c_per_kwh = price.price / 10.0; // €/MWh to cents/kWh
-                Price {
-                    validity: Local
-                        .timestamp_opt(price.timestamp as i64, 0)
-                        .unwrap()
-                        .fixed_offset(),
-                    price: if c_per_kwh > 0.0 {
-                        // Add VAT
-                        round_price(c_per_kwh * VAT)
-                    } else {
-                        // No VAT for negative prices
-                        c_per_kwh
-                    },
-                }
+            .map(|price| Price {
+                validity: Local
+                    .timestamp_opt(price.timestamp as i64, 0)
+                    .unwrap()
+                    .fixed_offset(),
+                price: c_per_kwh(price.price),
             })
             .collect())
     }
 }
 
-const VAT: f64 = 1.255;
+fn area_key(area: PriceArea) -> &'static str {
+    match area {
+        PriceArea::Ee => "ee",
+        PriceArea::Fi => "fi",
+        PriceArea::Lt => "lt",
+        PriceArea::Lv => "lv",
+    }
+}
 
 #[derive(Deserialize, Debug)]
 struct EleringResponse {
@@ -0,0 +1,210 @@
+use crate::domain::start_and_end_of_day;
+use crate::prices::{c_per_kwh, Price, PriceArea, PriceSource};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, Utc};
+use eyre::{eyre, Result, WrapErr};
+use roxmltree::{Document, Node};
+
+pub struct EntsoePriceClient {
+    client: reqwest::Client,
+    token: String,
+}
+
+impl EntsoePriceClient {
+    pub fn new(token: &str, timeout: std::time::Duration) -> Self {
+        Self {
+            client: reqwest::ClientBuilder::new()
+                .timeout(timeout)
+                .build()
+                .unwrap(),
+            token: token.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for EntsoePriceClient {
+    fn name(&self) -> &'static str {
+        "ENTSO-E"
+    }
+
+    async fn get_prices_for_date(&self, date: NaiveDate, area: PriceArea) -> Result<Vec<Price>> {
+        let (start, end) = start_and_end_of_day(date);
+        // End is the last second of the day, but the API wants the end of the last period
+        let end = end + Duration::seconds(1);
+        let domain = area_domain(area);
+        let response = self
+            .client
+            .get("https://web-api.tp.entsoe.eu/api")
+            .query(&[
+                ("securityToken", self.token.as_str()),
+                ("documentType", "A44"),
+                ("in_Domain", domain),
+                ("out_Domain", domain),
+                ("periodStart", &start.format("%Y%m%d%H%M").to_string()),
+                ("periodEnd", &end.format("%Y%m%d%H%M").to_string()),
+            ])
+            .send()
+            .await
+            .wrap_err_with(|| "Unable to request spot prices")?
+            .text()
+            .await
+            .wrap_err_with(|| "Unable to read spot prices")?;
+
+        Ok(parse_document(&response)?
+            .into_iter()
+            .filter(|(time, _)| start <= *time && *time < end)
+            .map(|(time, eur_per_mwh)| Price {
+                validity: time.with_timezone(&Local).fixed_offset(),
+                price: c_per_kwh(eur_per_mwh),
+            })
+            .collect())
+    }
+}
+
+/// EIC codes of the bidding zones
+fn area_domain(area: PriceArea) -> &'static str {
+    match area {
+        PriceArea::Ee => "10Y1001A1001A39I",
+        PriceArea::Fi => "10YFI-1--------U",
+        PriceArea::Lt => "10YLT-1001A0008Q",
+        PriceArea::Lv => "10YLV-1001A00074",
+    }
+}
+
+/// Parses a day-ahead prices document (A44) into ordered (start time, €/MWh) pairs
+fn parse_document(xml: &str) -> Result<Vec<(DateTime<Utc>, f64)>> {
+    let document = Document::parse(xml).wrap_err("Unable to parse spot prices")?;
+    let root = document.root_element();
+    if root.tag_name().name() == "Acknowledgement_MarketDocument" {
+        let reason = child(root, "Reason")
+            .and_then(|reason| child_text(reason, "text"))
+            .unwrap_or("unknown reason");
+        return Err(eyre!("ENTSO-E API returned error: {}", reason));
+    }
+
+    let mut periods = Vec::new();
+    for period in root
+        .descendants()
+        .filter(|node| node.has_tag_name("Period"))
+    {
+        let interval =
+            child(period, "timeInterval").ok_or_else(|| eyre!("Missing timeInterval"))?;
+        let start = parse_time(child_text(interval, "start"))?;
+        let end = parse_time(child_text(interval, "end"))?;
+        let resolution = match child_text(period, "resolution") {
+            Some("PT15M") => Duration::minutes(15),
+            Some("PT60M") => Duration::minutes(60),
+            other => return Err(eyre!("Unsupported resolution {:?}", other)),
+        };
+
+        let mut points = period
+            .children()
+            .filter(|node| node.has_tag_name("Point"))
+            .map(|point| {
+                let position = child_text(point, "position")
+                    .and_then(|position| position.parse::<i32>().ok())
+                    .ok_or_else(|| eyre!("Invalid point position"))?;
+                let price = child_text(point, "price.amount")
+                    .and_then(|price| price.parse::<f64>().ok())
+                    .ok_or_else(|| eyre!("Invalid point price"))?;
+                Ok((position, price))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        points.sort_by_key(|(position, _)| *position);
+
+        // Positions are omitted when the price is the same as in the previous position
+        let num_positions = ((end - start).num_minutes() / resolution.num_minutes()) as i32;
+        let mut points = points.into_iter().peekable();
+        let mut price = None;
+        for position in 1..=num_positions {
+            while let Some((_, next_price)) = points.next_if(|(p, _)| *p <= position) {
+                price = Some(next_price);
+            }
+            if let Some(price) = price {
+                periods.push((resolution, start + resolution * (position - 1), price));
+            }
+        }
+    }
+
+    // The same day can be published in several resolutions, so only the finest one is used
+    let finest = periods.iter().map(|(resolution, _, _)| *resolution).min();
+    let mut prices: Vec<(DateTime<Utc>, f64)> = Vec::new();
+    for (_, time, price) in periods
+        .into_iter()
+        .filter(|(resolution, _, _)| Some(*resolution) == finest)
+    {
+        // Keep the first price if periods overlap
+        if !prices.iter().any(|(t, _)| *t == time) {
+            prices.push((time, price));
+        }
+    }
+
+    prices.sort_by_key(|(time, _)| *time);
+    Ok(prices)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).and_then(|child| child.text())
+}
+
+fn parse_time(text: Option<&str>) -> Result<DateTime<Utc>> {
+    let text = text.ok_or_else(|| eyre!("Missing time"))?;
+    Ok(NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%MZ")
+        .wrap_err_with(|| format!("Invalid time {}", text))?
+        .and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_document;
+    use chrono::{DateTime, Duration, Utc};
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_hourly() {
+        let prices = parse_document(include_str!("fixtures/entsoe_pt60m.xml")).unwrap();
+        assert_eq!(prices.len(), 24);
+        assert_eq!(prices[0], (utc("2024-01-14T22:00:00Z"), 45.12));
+        assert_eq!(prices[1], (utc("2024-01-14T23:00:00Z"), 42.3));
+        assert_eq!(prices[23], (utc("2024-01-15T21:00:00Z"), -1.25));
+    }
+
+    /// The fixture has the same day as an hourly series too, before the quarterly one
+    #[test]
+    fn test_quarterly_with_gaps() {
+        let prices = parse_document(include_str!("fixtures/entsoe_pt15m.xml")).unwrap();
+        assert_eq!(prices.len(), 96);
+        for (i, (time, _)) in prices.iter().enumerate() {
+            assert_eq!(
+                *time,
+                utc("2025-10-14T21:00:00Z") + Duration::minutes(15 * i as i64)
+            );
+        }
+        // Positions 11-13 and 51 are missing and repeat the previous price
+        assert_eq!(prices[9].1, 23.23);
+        assert_eq!(prices[10].1, 23.23);
+        assert_eq!(prices[12].1, 23.23);
+        assert_eq!(prices[13].1, 24.59);
+        assert_eq!(prices[50].1, prices[49].1);
+        assert_ne!(prices[51].1, prices[50].1);
+        // Missing positions at the end of the period
+        assert_eq!(prices[95].1, 35.5);
+        assert_eq!(prices[0].1, 20.0);
+    }
+
+    #[test]
+    fn test_no_data() {
+        let error = parse_document(include_str!("fixtures/entsoe_no_data.xml")).unwrap_err();
+        assert!(error.to_string().contains("No matching data found"));
+    }
+}
@@ -1,49 +1,454 @@
+mod compare;
 mod elering;
+mod entsoe;
 mod porssisahko;
+mod store;
+mod validate;
+mod vat;
 
-use crate::domain::RelativeDate;
+use crate::config::{Config, PriceSourceConfig, PriceSourceKind};
 use crate::prices::elering::EleringPriceClient;
+use crate::prices::entsoe::EntsoePriceClient;
 use crate::prices::porssisahko::PorssisahkoPriceClient;
-use chrono::{DateTime, FixedOffset};
-use eyre::{Result, WrapErr};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate};
+use eyre::{eyre, Result};
 use serde::{Deserialize, Serialize};
 
+pub use crate::prices::compare::{compare_prices, PriceDifference, PriceMismatch};
+pub use crate::prices::store::{PriceStore, StoredPrices, PRICE_DIR_NAME};
+pub use crate::prices::validate::validate_prices;
+pub use crate::prices::vat::vat_percent;
+
 pub fn round_price(price: f64) -> f64 {
     (price * 1000.0).round() / 1000.0
 }
 
+/// Converts a spot price in €/MWh to c/kWh
+fn c_per_kwh(eur_per_mwh: f64) -> f64 {
+    round_price(eur_per_mwh / 10.0)
+}
+
+/// Adds VAT to prices in c/kWh. If `vat_percent` is not given, the VAT of the area at the time of
+/// each price is used.
+fn with_vat(prices: Vec<Price>, area: PriceArea, vat_percent: Option<f64>) -> Vec<Price> {
+    prices
+        .into_iter()
+        .map(|price| Price {
+            price: if price.price > 0.0 {
+                let vat = vat_percent
+                    .unwrap_or_else(|| vat::vat_percent(area, price.validity.date_naive()));
+                round_price(price.price * (1.0 + vat / 100.0))
+            } else {
+                // No VAT for negative prices
+                price.price
+            },
+            ..price
+        })
+        .collect()
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Price {
     pub validity: DateTime<FixedOffset>,
     pub price: f64,
 }
 
-pub struct PriceClient;
+/// Returns the resolution of the price series, i.e. the shortest interval between two
+/// consecutive prices. Defaults to one hour if it cannot be determined.
+pub fn slot_length(prices: &[Price]) -> Duration {
+    prices
+        .windows(2)
+        .map(|pair| pair[1].validity - pair[0].validity)
+        .filter(|length| *length > Duration::zero())
+        .min()
+        .unwrap_or_else(|| Duration::hours(1))
+}
+
+/// Bidding zone of the prices
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceArea {
+    Ee,
+    #[default]
+    Fi,
+    Lt,
+    Lv,
+}
+
+/// API to get the spot prices from
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Gets prices of `date` for `area` in c/kWh without VAT
+    async fn get_prices_for_date(&self, date: NaiveDate, area: PriceArea) -> Result<Vec<Price>>;
+}
+
+fn price_source(config: &PriceSourceConfig) -> Box<dyn PriceSource> {
+    let timeout = std::time::Duration::from_secs(config.timeout_secs);
+    match &config.source {
+        PriceSourceKind::Entsoe { token } => Box::new(EntsoePriceClient::new(token, timeout)),
+        PriceSourceKind::Elering => Box::new(EleringPriceClient::new(timeout)),
+        PriceSourceKind::Porssisahko => Box::new(PorssisahkoPriceClient::new(timeout)),
+    }
+}
+
+/// Prices of one day and the name of the source they were got from
+pub struct DayPrices {
+    pub prices: Vec<Price>,
+    pub source: &'static str,
+
+    /// Set if the prices could not be confirmed from another source
+    pub mismatch: Option<PriceMismatch>,
+}
+
+pub struct PriceClient {
+    sources: Vec<Box<dyn PriceSource>>,
+    tolerance: Option<f64>,
+    store: PriceStore,
+}
 
 impl PriceClient {
-    pub fn new() -> Self {
-        Self
+    pub fn new(config: &Config) -> Self {
+        Self::with_store(config, PriceStore::default())
+    }
+
+    pub fn with_store(config: &Config, store: PriceStore) -> Self {
+        Self {
+            sources: config.price_sources().iter().map(price_source).collect(),
+            tolerance: config.price_tolerance,
+            store,
+        }
     }
 
-    pub fn get_prices_for_date(&self, date: RelativeDate) -> Result<Vec<Price>> {
-        log::info!("Getting prices for {:?} from elering", date);
-        let elering_prices = EleringPriceClient::new().get_prices_for_date(date);
-        if let Ok(elering_prices) = elering_prices {
-            // DST transition day may have only 23 hours
-            if elering_prices.len() >= 23 {
-                return Ok(elering_prices);
+    /// Gets prices for `area` including VAT from the first source that returns a valid price
+    /// series for the whole day. If `vat_percent` is not given, the VAT of the area at the time of
+    /// each price is used. Prices in the price store are used instead of fetching them again.
+    ///
+    /// If a tolerance is configured, the prices are compared with the next valid source. If they
+    /// disagree, a third source decides which one is used. If that is not possible, the first
+    /// source is used and the disagreement is returned in `mismatch`.
+    pub async fn get_prices_for_date(
+        &self,
+        date: NaiveDate,
+        area: PriceArea,
+        vat_percent: Option<f64>,
+    ) -> Result<DayPrices> {
+        let mut sources = self.sources.iter();
+        let mut errors = Vec::new();
+        let mut next_valid = async || {
+            for source in sources.by_ref() {
+                match self
+                    .get_valid_prices(source.as_ref(), date, area, vat_percent)
+                    .await
+                {
+                    Ok(prices) => {
+                        return Some(DayPrices {
+                            prices,
+                            source: source.name(),
+                            mismatch: None,
+                        })
+                    }
+                    Err(error) => {
+                        log::warn!("Unable to get prices from {}: {:?}", source.name(), error);
+                        errors.push(format!("{}: {:#}", source.name(), error));
+                    }
+                }
+            }
+            None
+        };
+
+        let Some(first) = next_valid().await else {
+            return Err(eyre!(
+                "Unable to get prices for {} from any source\n{}",
+                date,
+                errors.join("\n")
+            ));
+        };
+        let Some(tolerance) = self.tolerance else {
+            return Ok(first);
+        };
+        let Some(second) = next_valid().await else {
+            log::warn!(
+                "No other source to cross-check the prices from {}",
+                first.source
+            );
+            return Ok(first);
+        };
+        let differences = compare_prices(&first.prices, &second.prices, tolerance);
+        if differences.is_empty() {
+            return Ok(first);
+        }
+        log::warn!(
+            "Prices from {} and {} differ in {} hours",
+            first.source,
+            second.source,
+            differences.len()
+        );
+
+        if let Some(third) = next_valid().await {
+            let agrees = |candidate: &DayPrices| {
+                compare_prices(&candidate.prices, &third.prices, tolerance).is_empty()
+            };
+            if agrees(&first) || agrees(&second) {
+                let candidate = if agrees(&first) { first } else { second };
+                log::info!(
+                    "Prices from {} agree with {}",
+                    candidate.source,
+                    third.source
+                );
+                return Ok(candidate);
             }
+            log::warn!("Prices from {} agree with neither", third.source);
         }
 
-        log::info!("Getting prices for {:?} from porssisahko.net", date);
-        PorssisahkoPriceClient::new()
-            .get_prices_for_date(date)
-            .wrap_err("Unable to get prices from porssisahko.net API")
+        Ok(DayPrices {
+            mismatch: Some(PriceMismatch {
+                sources: (first.source, second.source),
+                differences,
+            }),
+            ..first
+        })
+    }
+
+    async fn get_valid_prices(
+        &self,
+        source: &dyn PriceSource,
+        date: NaiveDate,
+        area: PriceArea,
+        vat_percent: Option<f64>,
+    ) -> Result<Vec<Price>> {
+        if let Some(prices) = self
+            .store
+            .load_with_vat(date, source.name(), area, vat_percent)
+        {
+            log::info!("Using stored prices for {} from {}", date, source.name());
+            return Ok(prices);
+        }
+
+        log::info!("Getting prices for {} from {}", date, source.name());
+        let prices = source.get_prices_for_date(date, area).await?;
+        validate_prices(&prices, date, &Local)?;
+        let stored = StoredPrices {
+            source: source.name().to_string(),
+            area,
+            prices,
+        };
+        if let Err(error) = self.store.save(date, &stored) {
+            log::warn!("Unable to store prices: {}", error);
+        }
+        Ok(with_vat(stored.prices, area, vat_percent))
+    }
+}
+
+/// `PriceClient` for code that is not running in an async runtime
+pub struct BlockingPriceClient {
+    client: PriceClient,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl BlockingPriceClient {
+    pub fn new(config: &Config) -> Result<Self> {
+        Self::with_store(config, PriceStore::default())
+    }
+
+    pub fn with_store(config: &Config, store: PriceStore) -> Result<Self> {
+        Ok(Self {
+            client: PriceClient::with_store(config, store),
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?,
+        })
+    }
+
+    pub fn get_prices_for_date(
+        &self,
+        date: NaiveDate,
+        area: PriceArea,
+        vat_percent: Option<f64>,
+    ) -> Result<DayPrices> {
+        self.runtime
+            .block_on(self.client.get_prices_for_date(date, area, vat_percent))
     }
 }
 
-impl Default for PriceClient {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::{BlockingPriceClient, Price, PriceArea, PriceClient, PriceSource, PriceStore};
+    use crate::domain::start_and_end_of_day;
+    use async_trait::async_trait;
+    use chrono::{Duration, Local, NaiveDate};
+    use eyre::{eyre, Result};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Source returning the same price for every hour of the day, or an error if `price` is None
+    struct StubSource {
+        name: &'static str,
+        price: Option<f64>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl PriceSource for StubSource {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn get_prices_for_date(&self, date: NaiveDate, _: PriceArea) -> Result<Vec<Price>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let price = self.price.ok_or_else(|| eyre!("No prices"))?;
+            Ok(day_prices(date, price))
+        }
+    }
+
+    /// Returns the source and the counter of its calls
+    fn stub_source(
+        name: &'static str,
+        price: Option<f64>,
+    ) -> (Box<dyn PriceSource>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let source = StubSource {
+            name,
+            price,
+            calls: calls.clone(),
+        };
+        (Box::new(source), calls)
+    }
+
+    fn day_prices(date: NaiveDate, price: f64) -> Vec<Price> {
+        let (start, end) = start_and_end_of_day(date);
+        let start = start.with_timezone(&Local).fixed_offset();
+        (0..)
+            .map(|hour| start + Duration::hours(hour))
+            .take_while(|validity| *validity < end)
+            .map(|validity| Price { validity, price })
+            .collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sahko-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+    }
+
+    #[test]
+    fn test_blocking_client() {
+        let (source, calls) = stub_source("Stub", Some(10.0));
+        let dir = temp_dir("blocking-client");
+        let client = BlockingPriceClient {
+            client: PriceClient {
+                sources: vec![source],
+                tolerance: None,
+                store: PriceStore::new(&dir),
+            },
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap(),
+        };
+
+        let day = client
+            .get_prices_for_date(date(), PriceArea::Fi, Some(10.0))
+            .unwrap();
+        assert_eq!(day.source, "Stub");
+        assert_eq!(day.mismatch, None);
+        assert_eq!(day.prices, day_prices(date(), 11.0));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // The stored prices are used, with the VAT applied again
+        let day = client
+            .get_prices_for_date(date(), PriceArea::Fi, Some(20.0))
+            .unwrap();
+        assert_eq!(day.prices, day_prices(date(), 12.0));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Gets the prices from the stub sources, with a tolerance of 0.5 c/kWh
+    fn cross_checked(name: &str, prices: &[(&'static str, Option<f64>)]) -> super::DayPrices {
+        let dir = temp_dir(name);
+        let client = PriceClient {
+            sources: prices
+                .iter()
+                .map(|(name, price)| stub_source(name, *price).0)
+                .collect(),
+            tolerance: Some(0.5),
+            store: PriceStore::new(&dir),
+        };
+        let day = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(client.get_prices_for_date(date(), PriceArea::Fi, Some(0.0)))
+            .unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+        day
+    }
+
+    #[test]
+    fn test_sources_agree() {
+        let day = cross_checked(
+            "sources-agree",
+            &[
+                ("First", Some(10.0)),
+                ("Second", Some(10.2)),
+                ("Third", None),
+            ],
+        );
+        assert_eq!(day.source, "First");
+        assert_eq!(day.mismatch, None);
+    }
+
+    #[test]
+    fn test_third_source_agrees_with_first() {
+        let day = cross_checked(
+            "third-agrees-with-first",
+            &[
+                ("Failing", None),
+                ("First", Some(10.0)),
+                ("Second", Some(20.0)),
+                ("Third", Some(10.1)),
+            ],
+        );
+        assert_eq!(day.source, "First");
+        assert_eq!(day.mismatch, None);
+    }
+
+    #[test]
+    fn test_third_source_agrees_with_second() {
+        let day = cross_checked(
+            "third-agrees-with-second",
+            &[
+                ("First", Some(10.0)),
+                ("Second", Some(20.0)),
+                ("Third", Some(20.1)),
+            ],
+        );
+        assert_eq!(day.source, "Second");
+        assert_eq!(day.prices, day_prices(date(), 20.0));
+        assert_eq!(day.mismatch, None);
+    }
+
+    #[test]
+    fn test_third_source_agrees_with_neither() {
+        let day = cross_checked(
+            "third-agrees-with-neither",
+            &[
+                ("First", Some(10.0)),
+                ("Second", Some(20.0)),
+                ("Third", Some(30.0)),
+            ],
+        );
+        assert_eq!(day.source, "First");
+        let mismatch = day.mismatch.unwrap();
+        assert_eq!(mismatch.sources, ("First", "Second"));
+        assert_eq!(mismatch.differences.len(), day.prices.len());
+        assert_eq!(mismatch.differences[0].prices, (10.0, 20.0));
     }
 }
@@ -1,38 +1,60 @@
-use crate::domain::RelativeDate;
-use crate::prices::Price;
-use chrono::{DateTime, Local, Utc};
-use eyre::{Result, WrapErr};
+use crate::domain::start_and_end_of_day;
+use crate::prices::{self, round_price, Price, PriceArea, PriceSource};
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use eyre::{eyre, Result, WrapErr};
 use serde::Deserialize;
 
-pub struct PorssisahkoPriceClient(reqwest::blocking::Client);
+pub struct PorssisahkoPriceClient(reqwest::Client);
 
 impl PorssisahkoPriceClient {
-    pub fn new() -> Self {
+    pub fn new(timeout: std::time::Duration) -> Self {
         Self(
-            reqwest::blocking::ClientBuilder::new()
-                .timeout(std::time::Duration::from_secs(10))
+            reqwest::ClientBuilder::new()
+                .timeout(timeout)
                 .build()
                 .unwrap(),
         )
     }
+}
+
+#[async_trait]
+impl PriceSource for PorssisahkoPriceClient {
+    fn name(&self) -> &'static str {
+        "porssisahko.net"
+    }
 
-    pub fn get_prices_for_date(&self, date: RelativeDate) -> Result<Vec<Price>> {
-        let (start, end) = date.to_start_and_end();
+    async fn get_prices_for_date(&self, date: NaiveDate, area: PriceArea) -> Result<Vec<Price>> {
+        if area != PriceArea::Fi {
+            return Err(eyre!("porssisahko.net only has prices for Finland"));
+        }
+        let (start, end) = start_and_end_of_day(date);
         let response = self
             .0
             .get("https://api.porssisahko.net/v1/latest-prices.json")
             .send()
+            .await
             .wrap_err_with(|| "Unable to request spot prices")?
             .json::<PorssisahkoResponse>()
+            .await
             .wrap_err_with(|| "Unable to parse spot prices")?;
 
         let mut prices = response
             .prices
             .into_iter()
             .filter(|price| start <= price.start_date && price.start_date < end)
-            .map(|price| Price {
-                validity: price.start_date.with_timezone(&Local).fixed_offset(),
-                price: price.price,
+            .map(|price| {
+                let validity = price.start_date.with_timezone(&Local).fixed_offset();
+                Price {
+                    validity,
+                    price: if price.price > 0.0 {
+                        // Remove the VAT included in the price
+                        let included = prices::vat_percent(area, validity.date_naive());
+                        round_price(price.price / (1.0 + included / 100.0))
+                    } else {
+                        price.price
+                    },
+                }
             })
             .collect::<Vec<_>>();
 
@@ -0,0 +1,111 @@
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fs::{create_dir_all, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::prices::{validate_prices, with_vat, Price, PriceArea};
+
+/// Prices of one day as returned by one source, without VAT
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredPrices {
+    pub source: String,
+    pub area: PriceArea,
+    pub prices: Vec<Price>,
+}
+
+/// Saves fetched prices per date and source, so that schedules can be recomputed without
+/// fetching the prices again
+pub struct PriceStore {
+    dir: PathBuf,
+}
+
+impl PriceStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the prices of `source` if they were stored for the same area
+    pub fn load(&self, date: NaiveDate, source: &str, area: PriceArea) -> Option<StoredPrices> {
+        let file = File::open(self.filename(date, source)).ok()?;
+        let stored: StoredPrices = serde_json::from_reader(file).ok()?;
+        (stored.area == area).then_some(stored)
+    }
+
+    /// Returns the valid prices of `source` for the whole day with VAT added as in
+    /// `PriceClient::get_prices_for_date`
+    pub fn load_with_vat(
+        &self,
+        date: NaiveDate,
+        source: &str,
+        area: PriceArea,
+        vat_percent: Option<f64>,
+    ) -> Option<Vec<Price>> {
+        let stored = self.load(date, source, area)?;
+        validate_prices(&stored.prices, date, &Local).ok()?;
+        Some(with_vat(stored.prices, area, vat_percent))
+    }
+
+    pub fn save(&self, date: NaiveDate, prices: &StoredPrices) -> std::io::Result<()> {
+        create_dir_all(&self.dir)?;
+        write!(
+            File::create(self.filename(date, &prices.source))?,
+            "{}",
+            serde_json::to_string_pretty(prices)?
+        )
+    }
+
+    fn filename(&self, date: NaiveDate, source: &str) -> PathBuf {
+        // Source names like "porssisahko.net" are not safe as such
+        let source = source
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        // Named differently from the earlier files, which had VAT included
+        self.dir.join(format!(
+            "spot_prices_{}_{}.json",
+            date.format("%Y-%m-%d"),
+            source
+        ))
+    }
+}
+
+impl Default for PriceStore {
+    fn default() -> Self {
+        Self::new(PRICE_DIR_NAME)
+    }
+}
+
+pub const PRICE_DIR_NAME: &str = "prices";
+
+#[cfg(test)]
+mod tests {
+    use super::{PriceStore, StoredPrices};
+    use crate::prices::{Price, PriceArea};
+    use chrono::{DateTime, NaiveDate};
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("sahko-prices-{}", std::process::id()));
+        let store = PriceStore::new(&dir);
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let prices = StoredPrices {
+            source: "porssisahko.net".to_string(),
+            area: PriceArea::Fi,
+            prices: vec![Price {
+                validity: DateTime::parse_from_rfc3339("2024-01-15T00:00:00+02:00").unwrap(),
+                price: 5.0,
+            }],
+        };
+        store.save(date, &prices).unwrap();
+
+        assert_eq!(
+            store.load(date, "porssisahko.net", PriceArea::Fi),
+            Some(prices)
+        );
+        assert_eq!(store.load(date, "Elering", PriceArea::Fi), None);
+        assert_eq!(store.load(date, "porssisahko.net", PriceArea::Ee), None);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
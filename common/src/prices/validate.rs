@@ -0,0 +1,141 @@
+use chrono::{DateTime, Days, FixedOffset, NaiveDate, NaiveTime, TimeZone};
+use eyre::{eyre, Result};
+
+use crate::prices::{slot_length, Price};
+
+/// Checks that `prices` are finite and cover `date` in time zone `tz` from start to end, in order,
+/// without gaps or duplicates
+pub fn validate_prices<Tz: TimeZone>(prices: &[Price], date: NaiveDate, tz: &Tz) -> Result<()> {
+    let start_of_day = |date: NaiveDate| -> Result<DateTime<FixedOffset>> {
+        date.and_time(NaiveTime::MIN)
+            .and_local_timezone(tz.clone())
+            .earliest()
+            .map(|time| time.fixed_offset())
+            .ok_or_else(|| eyre!("Invalid start of day {}", date))
+    };
+    let start = start_of_day(date)?;
+    let end = start_of_day(date + Days::new(1))?;
+
+    let (Some(first), Some(last)) = (prices.first(), prices.last()) else {
+        return Err(eyre!("No prices for {}", date));
+    };
+    if let Some(price) = prices
+        .iter()
+        .find(|price| price.validity.with_timezone(tz).date_naive() != date)
+    {
+        return Err(eyre!("Price at {} is not for {}", price.validity, date));
+    }
+    if let Some(price) = prices.iter().find(|price| !price.price.is_finite()) {
+        return Err(eyre!("Invalid price {} at {}", price.price, price.validity));
+    }
+
+    let slot = slot_length(prices);
+    for pair in prices.windows(2) {
+        let (a, b) = (pair[0].validity, pair[1].validity);
+        if a == b {
+            return Err(eyre!("Duplicate price at {}", a));
+        } else if b < a {
+            return Err(eyre!("Price at {} is out of order", b));
+        }
+    }
+    if let Some(pair) = prices
+        .windows(2)
+        .find(|pair| pair[1].validity - pair[0].validity != slot)
+    {
+        return Err(eyre!(
+            "Prices missing between {} and {}",
+            pair[0].validity,
+            pair[1].validity
+        ));
+    }
+
+    if first.validity != start {
+        return Err(eyre!(
+            "Prices start at {} instead of {}",
+            first.validity,
+            start
+        ));
+    }
+    if last.validity + slot != end {
+        return Err(eyre!(
+            "Prices end at {} instead of {}",
+            last.validity + slot,
+            end
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_prices;
+    use crate::prices::Price;
+    use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+
+    fn tz() -> FixedOffset {
+        FixedOffset::east_opt(2 * 3600).unwrap()
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+    }
+
+    fn make_prices(slot_minutes: i64) -> Vec<Price> {
+        let start = DateTime::parse_from_rfc3339("2024-01-15T00:00:00+02:00").unwrap();
+        (0..24 * 60 / slot_minutes)
+            .map(|i| Price {
+                validity: start + Duration::minutes(i * slot_minutes),
+                price: i as f64,
+            })
+            .collect()
+    }
+
+    fn error(prices: &[Price]) -> String {
+        validate_prices(prices, date(), &tz())
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_valid() {
+        assert!(validate_prices(&make_prices(60), date(), &tz()).is_ok());
+        assert!(validate_prices(&make_prices(15), date(), &tz()).is_ok());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(error(&[]).starts_with("No prices"));
+
+        let mut prices = make_prices(60);
+        prices.remove(5);
+        assert!(error(&prices).starts_with("Prices missing"));
+
+        let mut prices = make_prices(60);
+        prices.insert(5, prices[5]);
+        assert!(error(&prices).starts_with("Duplicate price"));
+
+        let mut prices = make_prices(60);
+        prices.swap(5, 6);
+        assert!(error(&prices).contains("out of order"));
+
+        let mut prices = make_prices(60);
+        prices.pop();
+        assert!(error(&prices).starts_with("Prices end"));
+
+        let mut prices = make_prices(60);
+        prices.remove(0);
+        assert!(error(&prices).starts_with("Prices start"));
+
+        let mut prices = make_prices(60);
+        for price in &mut prices {
+            price.validity += Duration::days(1);
+        }
+        assert!(error(&prices).contains("is not for"));
+
+        for invalid in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let mut prices = make_prices(60);
+            prices[5].price = invalid;
+            assert!(error(&prices).starts_with("Invalid price"));
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use chrono::NaiveDate;
+
+use crate::prices::PriceArea;
+
+/// VAT changes for electricity as (first day, percent), latest first
+const FI_VAT: &[((i32, u32, u32), f64)] = &[
+    ((2024, 9, 1), 25.5),
+    ((2023, 5, 1), 24.0),
+    ((2022, 12, 1), 10.0),
+    ((2013, 1, 1), 24.0),
+];
+
+const EE_VAT: &[((i32, u32, u32), f64)] = &[
+    ((2025, 7, 1), 24.0),
+    ((2024, 1, 1), 22.0),
+    ((2009, 7, 1), 20.0),
+];
+
+const LT_VAT: &[((i32, u32, u32), f64)] = &[((2009, 9, 1), 21.0)];
+
+const LV_VAT: &[((i32, u32, u32), f64)] = &[((2011, 1, 1), 21.0)];
+
+/// Returns the VAT percent for electricity in `area` on `date`
+pub fn vat_percent(area: PriceArea, date: NaiveDate) -> f64 {
+    let history = match area {
+        PriceArea::Ee => EE_VAT,
+        PriceArea::Fi => FI_VAT,
+        PriceArea::Lt => LT_VAT,
+        PriceArea::Lv => LV_VAT,
+    };
+    history
+        .iter()
+        .find(|((year, month, day), _)| {
+            NaiveDate::from_ymd_opt(*year, *month, *day).unwrap() <= date
+        })
+        .or(history.last())
+        .map(|(_, percent)| *percent)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prices::{vat_percent, PriceArea};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_vat_history() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(vat_percent(PriceArea::Fi, date(2022, 11, 30)), 24.0);
+        assert_eq!(vat_percent(PriceArea::Fi, date(2023, 1, 1)), 10.0);
+        assert_eq!(vat_percent(PriceArea::Fi, date(2024, 8, 31)), 24.0);
+        assert_eq!(vat_percent(PriceArea::Fi, date(2024, 9, 1)), 25.5);
+        assert_eq!(vat_percent(PriceArea::Ee, date(2024, 6, 1)), 22.0);
+        assert_eq!(vat_percent(PriceArea::Lv, date(2000, 1, 1)), 21.0);
+    }
+}
@@ -0,0 +1,1859 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, TimeZone};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs::{create_dir_all, File};
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+mod strategy;
+
+use crate::config::{Config, OutputConfig, ScheduleConfig, Strategy, Tariff};
+use crate::overrides::TimedOverrides;
+use crate::prices::{slot_length, Price, PriceStore};
+use crate::schedule::strategy::strategy_for;
+pub use crate::schedule::strategy::{
+    CheapestStrategy, ContiguousStrategy, LimitsStrategy, SchedulingStrategy, ThresholdStrategy,
+    Window,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinSchedule {
+    pub name: String,
+
+    /// Switches controlled by the schedule, so that they are controlled until the end of the day
+    /// even if the schedule is removed from the config. Old schedule files have a GPIO `pin`.
+    #[serde(alias = "pin", deserialize_with = "deserialize_outputs")]
+    pub outputs: Vec<OutputConfig>,
+
+    /// Length of one slot in `on_slots`. Old schedule files don't have this and are hourly.
+    #[serde(
+        default = "default_slot_minutes",
+        deserialize_with = "deserialize_slot_minutes"
+    )]
+    pub slot_minutes: u32,
+
+    /// Starts of the slots when the pin is on
+    #[serde(alias = "on_hours")]
+    pub on_slots: Vec<DateTime<FixedOffset>>,
+
+    /// Power of the controlled device in kW, if known
+    #[serde(default)]
+    pub power_kw: Option<f64>,
+
+    /// Manual changes that take precedence over `on_slots`
+    #[serde(default)]
+    pub overrides: Vec<SlotOverride>,
+}
+
+/// A manual change to the computed schedule of one slot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotOverride {
+    /// Start of the slot
+    pub slot: DateTime<FixedOffset>,
+
+    /// Whether the pin is forced on or off
+    pub on: bool,
+
+    /// Who made the change
+    pub author: String,
+
+    /// When the change was made
+    pub timestamp: DateTime<FixedOffset>,
+}
+
+fn default_slot_minutes() -> u32 {
+    60
+}
+
+/// Accepts only slot lengths that divide an hour evenly, so that slots can be aligned to hours
+fn deserialize_slot_minutes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let minutes = u32::deserialize(deserializer)?;
+    if minutes == 0 || 60 % minutes != 0 {
+        return Err(D::Error::custom(format!(
+            "Invalid slot length {} minutes",
+            minutes
+        )));
+    }
+    Ok(minutes)
+}
+
+/// Accepts a list of outputs or a GPIO pin
+fn deserialize_outputs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<OutputConfig>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Outputs {
+        Pin(u8),
+        Outputs(Vec<OutputConfig>),
+    }
+    Ok(match Outputs::deserialize(deserializer)? {
+        Outputs::Pin(pin) => vec![OutputConfig {
+            pin: Some(pin),
+            ..Default::default()
+        }],
+        Outputs::Outputs(outputs) => outputs,
+    })
+}
+
+impl PinSchedule {
+    pub fn compute(config: &ScheduleConfig, prices: &[Price]) -> Self {
+        Self::compute_window(config, prices, Duration::hours(24))
+    }
+
+    /// Computes a schedule over a window of arbitrary length. `min_on_hours` and `max_on_hours`
+    /// are scaled from 24 hours to the window length.
+    pub fn compute_window(config: &ScheduleConfig, prices: &[Price], window: Duration) -> Self {
+        Self::compute_with_strategy(config, prices, window, strategy_for(config.strategy()))
+    }
+
+    /// Computes a schedule with the given strategy instead of the one selected in `config`
+    pub fn compute_with_strategy(
+        config: &ScheduleConfig,
+        prices: &[Price],
+        window: Duration,
+        strategy: &dyn SchedulingStrategy,
+    ) -> Self {
+        Self::compute_slots(config, prices, window, strategy, None)
+    }
+
+    /// Computes a schedule over the slots of `prices` that continues from the slots in `rolling`.
+    /// `min_on_hours` and `max_on_hours` apply to each 24 hours that ends within the window,
+    /// counting the slots that were on before it, and the manual overrides of the window are kept.
+    fn compute_rolling(config: &ScheduleConfig, prices: &[Price], rolling: &Rolling) -> Self {
+        let slot = slot_length(prices);
+        let window = match (prices.first(), prices.last()) {
+            (Some(first), Some(last)) => last.validity + slot - first.validity,
+            _ => Duration::zero(),
+        };
+        let strategy = strategy_for(config.strategy());
+        Self::compute_slots(config, prices, window, strategy, Some(rolling))
+    }
+
+    fn compute_slots(
+        config: &ScheduleConfig,
+        prices: &[Price],
+        window: Duration,
+        strategy: &dyn SchedulingStrategy,
+        rolling: Option<&Rolling>,
+    ) -> Self {
+        let slot = slot_length(prices);
+
+        let mut prices = prices.to_vec();
+        prices.sort_by_key(|price| price.validity);
+        let window = Window::new(config, &prices, slot, window);
+
+        let mut on = strategy.select(config, &window);
+
+        // Forced and disallowed slots override the strategy
+        let allowed = prices
+            .iter()
+            .map(|price| config.is_allowed(&price.validity))
+            .collect::<Vec<_>>();
+        let forced = prices
+            .iter()
+            .map(|price| config.is_forced_on(&price.validity))
+            .collect::<Vec<_>>();
+        for (i, on) in on.iter_mut().enumerate() {
+            *on = forced[i] || (*on && allowed[i]);
+        }
+
+        // Keep the manual overrides and the limits of each 24 hours of a rolling window
+        if let Some(rolling) = rolling {
+            let mut fixed = forced.clone();
+            for (i, price) in prices.iter().enumerate() {
+                if let Some(o) = rolling.overrides.iter().find(|o| o.slot == price.validity) {
+                    on[i] = o.on;
+                    fixed[i] = true;
+                }
+            }
+            if matches!(config.strategy(), Strategy::Limits | Strategy::Cheapest) {
+                let day = Window::new(config, &prices, slot, Duration::hours(24));
+                let limits = RollingLimits {
+                    allowed: &allowed,
+                    fixed: &fixed,
+                    min_on: day.min_on_slots(config),
+                    max_on: day.daily_hours_to_slots(config.max_on_hours),
+                    earlier_start: rolling.earlier_start,
+                    earlier_on_slots: &rolling.earlier_on_slots,
+                };
+                limit_rolling_hours(&prices, slot, &mut on, &limits);
+            }
+        }
+
+        // Turn on the cheapest slots in gaps longer than `max_consecutive_off_hours`
+        let max_off_slots = config
+            .max_consecutive_off_hours
+            .map(|hours| window.hours_to_slots(hours));
+        if let Some(max_off_slots) = max_off_slots {
+            fill_long_gaps(&prices, &mut on, &allowed, max_off_slots);
+        }
+
+        // Merge or drop runs to satisfy `max_switches_per_day` and `min_consecutive_off_hours`
+        if config.max_switches_per_day.is_some() || config.min_consecutive_off_hours.is_some() {
+            let num_on = on.iter().filter(|on| **on).count();
+            let limits = SwitchLimits {
+                allowed: &allowed,
+                forced: &forced,
+                max_switches: config
+                    .max_switches_per_day
+                    .map(|max| (f64::from(max) * window.days()).round() as usize),
+                min_off: config
+                    .min_consecutive_off_hours
+                    .map_or(0, |hours| window.hours_to_slots(hours)),
+                max_off: max_off_slots,
+                min_on: window.min_on_slots(config).min(num_on),
+            };
+            limit_switches(&prices, &mut on, &limits);
+        }
+
+        Self {
+            name: config.name.clone(),
+            outputs: config.outputs().to_vec(),
+            slot_minutes: slot.num_minutes() as u32,
+            on_slots: prices
+                .iter()
+                .zip(on)
+                .filter_map(|(price, on)| if on { Some(price.validity) } else { None })
+                .collect(),
+            power_kw: config.power_kw,
+            overrides: Vec::new(),
+        }
+    }
+
+    pub fn slot_length(&self) -> Duration {
+        Duration::minutes(self.slot_minutes.into())
+    }
+
+    /// Total time the pin is on
+    pub fn on_duration(&self) -> Duration {
+        self.slot_length() * self.effective_on_slots().len() as i32
+    }
+
+    /// Whether the pin is on at `now`, taking the overrides into account
+    pub fn is_on<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> bool {
+        let slot = self.slot_length();
+        let contains_now = |start: &DateTime<FixedOffset>| *start <= *now && *now < *start + slot;
+        match self.overrides.iter().find(|o| contains_now(&o.slot)) {
+            Some(slot_override) => slot_override.on,
+            None => self.on_slots.iter().any(contains_now),
+        }
+    }
+
+    /// Returns the override of the slot starting at `slot`, if any
+    pub fn override_for(&self, slot: &DateTime<FixedOffset>) -> Option<&SlotOverride> {
+        self.overrides.iter().find(|o| o.slot == *slot)
+    }
+
+    /// Starts of the slots when the pin is on, taking the overrides into account
+    pub fn effective_on_slots(&self) -> Vec<DateTime<FixedOffset>> {
+        let mut slots = self
+            .on_slots
+            .iter()
+            .filter(|slot| self.override_for(slot).is_none_or(|o| o.on))
+            .chain(
+                self.overrides
+                    .iter()
+                    .filter(|o| o.on && !self.on_slots.contains(&o.slot))
+                    .map(|o| &o.slot),
+            )
+            .copied()
+            .collect::<Vec<_>>();
+        slots.sort();
+        slots
+    }
+
+    /// Manually sets the state of the slot starting at `slot`. An override is only stored if the
+    /// state differs from the computed one, and an existing override with the same state is kept.
+    pub fn set_slot(
+        &mut self,
+        slot: DateTime<FixedOffset>,
+        on: bool,
+        author: &str,
+        timestamp: DateTime<FixedOffset>,
+    ) {
+        if self.override_for(&slot).map(|o| o.on) == Some(on) {
+            return;
+        }
+        self.overrides.retain(|o| o.slot != slot);
+        if self.on_slots.contains(&slot) != on {
+            self.overrides.push(SlotOverride {
+                slot,
+                on,
+                author: author.to_string(),
+                timestamp,
+            });
+            self.overrides.sort_by_key(|o| o.slot);
+        }
+    }
+
+    /// Energy used while the pin is on, in kWh
+    pub fn energy_kwh(&self) -> Option<f64> {
+        self.power_kw
+            .map(|kw| kw * self.on_duration().num_minutes() as f64 / 60.0)
+    }
+
+    /// Cost of the energy used while the pin is on, in euros
+    pub fn cost_eur(&self, prices: &[Price]) -> Option<f64> {
+        let kw = self.power_kw?;
+        let price_hours = slot_length(prices).num_minutes() as f64 / 60.0;
+        let cents = prices
+            .iter()
+            .filter(|price| self.is_on(&price.validity))
+            .map(|price| price.price * kw * price_hours)
+            .sum::<f64>();
+        Some(cents / 100.0)
+    }
+
+    pub fn avg_price(&self, prices: &[Price], on: bool) -> f64 {
+        let selected_prices = prices
+            .iter()
+            .filter(|price| self.is_on(&price.validity) == on)
+            .map(|price| price.price)
+            .collect::<Vec<_>>();
+
+        if selected_prices.is_empty() {
+            0.0
+        } else {
+            selected_prices.iter().sum::<f64>() / selected_prices.len() as f64
+        }
+    }
+}
+
+/// Turns on the cheapest set of slots so that no run of off slots is longer than `max_off`.
+/// `prices` must be in time order and `on` tells which slots are already on. Slots that are
+/// not `allowed` are never turned on, even if that leaves a gap too long.
+fn fill_long_gaps(prices: &[Price], on: &mut [bool], allowed: &[bool], max_off: usize) {
+    let mut gap_start = 0;
+    while gap_start < on.len() {
+        if on[gap_start] {
+            gap_start += 1;
+            continue;
+        }
+        let gap_end = (gap_start..on.len()).find(|&i| on[i]).unwrap_or(on.len());
+        let gap = &prices[gap_start..gap_end];
+        if gap.len() > max_off {
+            // cost[i] is the lowest cost of turning on slot i and enough slots before it so
+            // that there are at most `max_off` off slots between the gap start and slot i.
+            // prev[i] is the previous slot turned on in that solution.
+            let mut cost = vec![0.0; gap.len()];
+            let mut prev: Vec<Option<usize>> = vec![None; gap.len()];
+            for i in 0..gap.len() {
+                // Slots near the gap start don't need an earlier slot to be on
+                let from_start = if i <= max_off {
+                    Some((0.0, None))
+                } else {
+                    None
+                };
+                let (best_cost, best_prev) = (i.saturating_sub(max_off + 1)..i)
+                    .map(|j| (cost[j], Some(j)))
+                    .chain(from_start)
+                    .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                    .unwrap();
+                let price = if allowed[gap_start + i] {
+                    gap[i].price
+                } else {
+                    f64::INFINITY
+                };
+                cost[i] = price + best_cost;
+                prev[i] = best_prev;
+            }
+            let mut last = (gap.len() - max_off - 1..gap.len())
+                .min_by(|&a, &b| cost[a].partial_cmp(&cost[b]).unwrap());
+            while let Some(i) = last {
+                on[gap_start + i] = allowed[gap_start + i];
+                last = prev[i];
+            }
+        }
+        gap_start = gap_end;
+    }
+}
+
+/// What a rolling window continues from
+struct Rolling {
+    /// Start of the time covered by `earlier_on_slots`
+    earlier_start: DateTime<FixedOffset>,
+
+    /// Starts of the slots that were on before the window, taking the overrides into account
+    earlier_on_slots: Vec<DateTime<FixedOffset>>,
+
+    /// Manual changes to the slots of the window
+    overrides: Vec<SlotOverride>,
+}
+
+struct RollingLimits<'a> {
+    /// Slots that may be turned on
+    allowed: &'a [bool],
+    /// Slots that must keep their state
+    fixed: &'a [bool],
+    min_on: usize,
+    max_on: usize,
+    earlier_start: DateTime<FixedOffset>,
+    earlier_on_slots: &'a [DateTime<FixedOffset>],
+}
+
+/// Turns off the most expensive slots of each 24 hours with more than `max_on` slots on, and then
+/// turns on the cheapest slots of each 24 hours with fewer than `min_on`, as long as no 24 hours
+/// goes over `max_on`. The 24 hours end at the end of each slot of the window, and the earlier
+/// slots count towards them. `min_on` is not checked before `earlier_start`, where the earlier
+/// slots are not known.
+fn limit_rolling_hours(prices: &[Price], slot: Duration, on: &mut [bool], limits: &RollingLimits) {
+    let Some(window_start) = prices.first().map(|price| price.validity) else {
+        return;
+    };
+    let day_start = |end: usize| prices[end].validity + slot - Duration::hours(24);
+
+    // starts[end] is the first slot of the 24 hours ending with slot `end`, and counts[end] the
+    // number of slots on in them
+    let starts = (0..prices.len())
+        .map(|end| {
+            let start = day_start(end);
+            prices
+                .iter()
+                .position(|price| start <= price.validity)
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let mut counts = (0..prices.len())
+        .map(|end| {
+            let start = day_start(end);
+            let earlier = limits
+                .earlier_on_slots
+                .iter()
+                .filter(|slot| start <= **slot && **slot < window_start)
+                .count();
+            earlier + on[starts[end]..=end].iter().filter(|on| **on).count()
+        })
+        .collect::<Vec<_>>();
+    let starts = starts.as_slice();
+    let days_with = |i: usize| (i..prices.len()).take_while(move |&end| starts[end] <= i);
+    let by_price = |a: &usize, b: &usize| prices[*a].price.partial_cmp(&prices[*b].price).unwrap();
+
+    for end in 0..prices.len() {
+        while counts[end] > limits.max_on {
+            let Some(i) = (starts[end]..=end)
+                .filter(|&i| on[i] && !limits.fixed[i])
+                .max_by(by_price)
+            else {
+                break;
+            };
+            on[i] = false;
+            days_with(i).for_each(|end| counts[end] -= 1);
+        }
+    }
+
+    for end in 0..prices.len() {
+        if day_start(end) < limits.earlier_start {
+            continue;
+        }
+        while counts[end] < limits.min_on {
+            let Some(i) = (starts[end]..=end)
+                .filter(|&i| !on[i] && limits.allowed[i] && !limits.fixed[i])
+                .filter(|&i| days_with(i).all(|end| counts[end] < limits.max_on))
+                .min_by(by_price)
+            else {
+                break;
+            };
+            on[i] = true;
+            days_with(i).for_each(|end| counts[end] += 1);
+        }
+    }
+}
+
+struct SwitchLimits<'a> {
+    /// Slots that may be turned on
+    allowed: &'a [bool],
+    /// Slots that must stay on
+    forced: &'a [bool],
+    max_switches: Option<usize>,
+    min_off: usize,
+    max_off: Option<usize>,
+    min_on: usize,
+}
+
+/// Returns the on runs as `(start, end)` index pairs, `end` exclusive
+fn on_runs(on: &[bool]) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < on.len() {
+        if on[i] {
+            let start = i;
+            while i < on.len() && on[i] {
+                i += 1;
+            }
+            runs.push((start, i));
+        } else {
+            i += 1;
+        }
+    }
+    runs
+}
+
+/// Number of times the state changes during the window
+fn count_switches(on: &[bool]) -> usize {
+    on.windows(2).filter(|pair| pair[0] != pair[1]).count()
+}
+
+/// Merges runs by turning on the gap between them, or drops runs, until there are at most
+/// `max_switches` switches and no gap in the middle of the window is shorter than `min_off`.
+/// Each step picks the change that adds the least cost.
+fn limit_switches(prices: &[Price], on: &mut [bool], limits: &SwitchLimits) {
+    let sum = |range: &Range<usize>| prices[range.clone()].iter().map(|p| p.price).sum::<f64>();
+    loop {
+        let runs = on_runs(on);
+        let short_gap = runs
+            .windows(2)
+            .position(|pair| pair[1].0 - pair[0].1 < limits.min_off);
+        let too_many_switches = limits
+            .max_switches
+            .is_some_and(|max| count_switches(on) > max);
+        if short_gap.is_none() && !too_many_switches {
+            break;
+        }
+
+        // Candidate changes as (added cost, slots, new state). If there's a short gap, only
+        // consider changes that remove it.
+        let mut candidates: Vec<(f64, Range<usize>, bool)> = Vec::new();
+        for (i, pair) in runs.windows(2).enumerate() {
+            let gap = pair[0].1..pair[1].0;
+            if short_gap.is_none_or(|g| g == i) && gap.clone().all(|j| limits.allowed[j]) {
+                candidates.push((sum(&gap), gap, true));
+            }
+        }
+        let num_on = on.iter().filter(|on| **on).count();
+        for (i, &(start, end)) in runs.iter().enumerate() {
+            let gap_start = if i == 0 { 0 } else { runs[i - 1].1 };
+            let gap_end = runs.get(i + 1).map_or(on.len(), |run| run.0);
+            if short_gap.is_none_or(|g| g == i || g + 1 == i)
+                && !(start..end).any(|j| limits.forced[j])
+                && num_on - (end - start) >= limits.min_on
+                && limits.max_off.is_none_or(|max| gap_end - gap_start <= max)
+            {
+                candidates.push((-sum(&(start..end)), start..end, false));
+            }
+        }
+
+        let Some((_, range, state)) = candidates
+            .into_iter()
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+        else {
+            // Constraints can't be satisfied
+            break;
+        };
+        for i in range {
+            on[i] = state;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub pins: Vec<PinSchedule>,
+
+    /// Spot prices
+    pub prices: Vec<Price>,
+
+    /// Costs added to the spot prices when the schedule was computed
+    #[serde(default)]
+    pub tariff: Option<Tariff>,
+
+    /// Name of the source the prices were got from
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+impl Schedule {
+    /// Computes the schedules from spot prices, using the effective price with the tariff
+    pub fn compute(config: &Config, prices: &[Price]) -> Self {
+        let effective_prices = effective_prices(&config.tariff, prices);
+        Self {
+            pins: config
+                .schedules
+                .iter()
+                .map(|config| PinSchedule::compute(config, &effective_prices))
+                .collect(),
+            prices: prices.to_vec(),
+            tariff: config.tariff.clone(),
+            source: None,
+        }
+    }
+
+    /// Computes the schedule of the day after `previous` from the day's spot prices. Pins with
+    /// `rolling_window` set are optimized together with the slots of `previous` that haven't
+    /// started at `now`, which are updated, continuing from `earlier`, the schedule of the day
+    /// before `previous`.
+    pub fn compute_for_date<Tz: TimeZone>(
+        config: &Config,
+        prices: &[Price],
+        earlier: Option<&Schedule>,
+        previous: Option<&mut Schedule>,
+        now: &DateTime<Tz>,
+    ) -> Self {
+        let schedule = Self::compute(config, prices);
+        match previous {
+            Some(previous) => {
+                let mut days = [previous.clone(), schedule];
+                Self::compute_rolling(config, earlier, &mut days, now);
+                let [updated_previous, schedule] = days;
+                *previous = updated_previous;
+                schedule
+            }
+            None => {
+                let mut days = [schedule];
+                Self::compute_rolling(config, None, &mut days, now);
+                let [schedule] = days;
+                schedule
+            }
+        }
+    }
+
+    /// Replaces the prices of the schedule of `date` with the ones saved in `store` from the same
+    /// source, with the area and VAT of `config`. The prices are kept as they are if they were not
+    /// saved, like for the schedules computed before the prices were saved.
+    pub fn reload_prices(&mut self, config: &Config, store: &PriceStore, date: NaiveDate) {
+        let prices = self.source.as_deref().and_then(|source| {
+            store.load_with_vat(date, source, config.price_area, config.vat_percent)
+        });
+        match prices {
+            Some(prices) => self.prices = prices,
+            None => log::warn!(
+                "No saved prices for {}, using the prices of the schedule",
+                date
+            ),
+        }
+    }
+
+    /// Spot prices with the tariff costs added
+    pub fn effective_prices(&self) -> Vec<Price> {
+        effective_prices(&self.tariff, &self.prices)
+    }
+
+    /// Copy of the schedule where the boosts and pauses override the slots they overlap, taking
+    /// precedence over the computed slots and the manual changes. Used wherever the state of the
+    /// pins is shown or set.
+    pub fn with_timed_overrides(&self, timed_overrides: &TimedOverrides) -> Self {
+        let mut schedule = self.clone();
+        let (Some(first), Some(last)) = (self.prices.first(), self.prices.last()) else {
+            return schedule;
+        };
+        let end_of_day = last.validity + slot_length(&self.prices);
+        for pin in &mut schedule.pins {
+            let slot = pin.slot_length();
+            let mut start = first.validity;
+            while start < end_of_day {
+                if let Some(timed_override) = timed_overrides.covering(&pin.name, &start, slot) {
+                    pin.overrides.retain(|o| o.slot != start);
+                    pin.overrides.push(SlotOverride {
+                        slot: start,
+                        on: timed_override.on(),
+                        author: timed_override.author.clone(),
+                        timestamp: timed_override.start,
+                    });
+                }
+                start += slot;
+            }
+            pin.overrides.sort_by_key(|o| o.slot);
+        }
+        schedule
+    }
+
+    /// Optimizes the pins with `rolling_window` set over the slots of `days`, schedules of
+    /// consecutive days in time order, that haven't started at `now`. The slots on in `earlier`,
+    /// the schedule of the day before, and the slots that have started count against the limits
+    /// of each 24 hours, and the manual overrides are kept.
+    pub fn compute_rolling<Tz: TimeZone>(
+        config: &Config,
+        earlier: Option<&Schedule>,
+        days: &mut [Schedule],
+        now: &DateTime<Tz>,
+    ) {
+        let has_started = |start: &DateTime<FixedOffset>| *start <= *now;
+        for config in config
+            .schedules
+            .iter()
+            .filter(|config| config.rolling_window)
+        {
+            let find =
+                |schedule: &Schedule| schedule.pins.iter().position(|pin| pin.name == config.name);
+            let pins = days
+                .iter()
+                .enumerate()
+                .filter_map(|(day, schedule)| Some((day, find(schedule)?)))
+                .collect::<Vec<_>>();
+            // The slots of the days must line up to be optimized together
+            let slot_lengths = pins
+                .iter()
+                .map(|&(day, _)| slot_length(&days[day].prices))
+                .collect::<Vec<_>>();
+            if pins.is_empty() || slot_lengths.windows(2).any(|pair| pair[0] != pair[1]) {
+                continue;
+            }
+
+            let earlier_pin = earlier.and_then(|earlier| Some(&earlier.pins[find(earlier)?]));
+            let first_day = &days[pins[0].0];
+            let Some(earlier_start) = earlier
+                .filter(|_| earlier_pin.is_some())
+                .unwrap_or(first_day)
+                .prices
+                .first()
+                .map(|price| price.validity)
+            else {
+                continue;
+            };
+            let mut rolling = Rolling {
+                earlier_start,
+                earlier_on_slots: earlier_pin
+                    .map(|pin| pin.effective_on_slots())
+                    .unwrap_or_default(),
+                overrides: Vec::new(),
+            };
+            let mut window_prices = Vec::new();
+            for &(day, pin) in &pins {
+                let pin = &days[day].pins[pin];
+                rolling.earlier_on_slots.extend(
+                    pin.effective_on_slots()
+                        .into_iter()
+                        .filter(|start| has_started(start)),
+                );
+                rolling.overrides.extend(
+                    pin.overrides
+                        .iter()
+                        .filter(|o| !has_started(&o.slot))
+                        .cloned(),
+                );
+                window_prices.extend(
+                    days[day]
+                        .effective_prices()
+                        .into_iter()
+                        .filter(|price| !has_started(&price.validity)),
+                );
+            }
+            if window_prices.is_empty() {
+                continue;
+            }
+
+            let computed = PinSchedule::compute_rolling(config, &window_prices, &rolling);
+            for &(day, pin) in &pins {
+                let schedule = &mut days[day];
+                let slot = slot_lengths[0];
+                let in_day = |start: &DateTime<FixedOffset>| {
+                    schedule
+                        .prices
+                        .first()
+                        .is_some_and(|first| first.validity <= *start)
+                        && schedule
+                            .prices
+                            .last()
+                            .is_some_and(|last| *start < last.validity + slot)
+                };
+                let pin = &mut schedule.pins[pin];
+                pin.on_slots = pin
+                    .on_slots
+                    .iter()
+                    .filter(|start| has_started(start))
+                    .chain(computed.on_slots.iter().filter(|start| in_day(start)))
+                    .copied()
+                    .collect();
+            }
+        }
+    }
+
+    /// Computes the schedules of `days`, consecutive days in time order, again with the same
+    /// prices and the current config. Slots that have started at `now` and the manual overrides
+    /// are kept. Pins with `rolling_window` set are optimized over the days together, continuing
+    /// from `earlier`, the schedule of the day before.
+    pub fn recompute<Tz: TimeZone>(
+        config: &Config,
+        earlier: Option<&Schedule>,
+        days: &[Schedule],
+        now: &DateTime<Tz>,
+    ) -> Vec<Self> {
+        let mut schedules = days
+            .iter()
+            .map(|day| day.recompute_day(config, now))
+            .collect::<Vec<_>>();
+        Self::compute_rolling(config, earlier, &mut schedules, now);
+        schedules
+    }
+
+    fn recompute_day<Tz: TimeZone>(&self, config: &Config, now: &DateTime<Tz>) -> Self {
+        let mut schedule = Self::compute(config, &self.prices);
+        for pin in &mut schedule.pins {
+            let Some(old_pin) = self.pins.iter().find(|old_pin| old_pin.name == pin.name) else {
+                continue;
+            };
+            pin.overrides.clone_from(&old_pin.overrides);
+            let has_started = |start: &DateTime<FixedOffset>| *start <= *now;
+            pin.on_slots = old_pin
+                .on_slots
+                .iter()
+                .filter(|start| has_started(start))
+                .chain(pin.on_slots.iter().filter(|start| !has_started(start)))
+                .copied()
+                .collect();
+        }
+        schedule.source.clone_from(&self.source);
+        schedule
+    }
+
+    pub fn avg_price(&self) -> f64 {
+        // This assumes that all price spans are equal length and cover the whole day
+        let prices = self.effective_prices();
+        prices.iter().map(|price| price.price).sum::<f64>() / prices.len() as f64
+    }
+
+    /// Total energy used by the pins with a known power, in kWh
+    pub fn energy_kwh(&self) -> Option<f64> {
+        sum_known(self.pins.iter().map(|pin| pin.energy_kwh()))
+    }
+
+    /// Total cost of the energy used by the pins with a known power, in euros
+    pub fn cost_eur(&self) -> Option<f64> {
+        let prices = self.effective_prices();
+        sum_known(self.pins.iter().map(|pin| pin.cost_eur(&prices)))
+    }
+
+    /// Average price over a slot. If the prices are coarser than the slot, returns the price
+    /// that is in effect at the start of the slot.
+    pub fn avg_price_for_slot<Tz: TimeZone>(
+        &self,
+        start: DateTime<Tz>,
+        length: Duration,
+    ) -> Option<f64> {
+        let end = start.clone() + length;
+        let prices = self.effective_prices();
+        let slot_prices = prices
+            .iter()
+            .filter(|price| start <= price.validity && price.validity < end)
+            .map(|price| price.price)
+            .collect::<Vec<_>>();
+        if slot_prices.is_empty() {
+            prices
+                .iter()
+                .filter(|price| price.validity <= start)
+                .max_by_key(|price| price.validity)
+                .map(|price| price.price)
+        } else {
+            Some(slot_prices.iter().sum::<f64>() / slot_prices.len() as f64)
+        }
+    }
+
+    pub fn load_for_date(date: NaiveDate) -> Option<Self> {
+        Self::load_from(SCHEDULE_DIR_NAME, date)
+    }
+
+    /// Loads the schedule for `date` from the directory `dir`
+    pub fn load_from<P: AsRef<Path>>(dir: P, date: NaiveDate) -> Option<Self> {
+        let file = File::open(schedule_filename(dir.as_ref(), date)).ok()?;
+        serde_json::from_reader(file).ok()
+    }
+
+    pub fn write_to_file(&self, date: NaiveDate) -> std::io::Result<()> {
+        self.write_to(SCHEDULE_DIR_NAME, date)
+    }
+
+    /// Writes the schedule for `date` to the directory `dir`
+    pub fn write_to<P: AsRef<Path>>(&self, dir: P, date: NaiveDate) -> std::io::Result<()> {
+        create_dir_all(&dir)?;
+        write!(
+            File::create(schedule_filename(dir.as_ref(), date))?,
+            "{}",
+            serde_json::to_string_pretty(self)?
+        )
+    }
+}
+
+fn effective_prices(tariff: &Option<Tariff>, prices: &[Price]) -> Vec<Price> {
+    match tariff {
+        Some(tariff) => tariff.apply(prices),
+        None => prices.to_vec(),
+    }
+}
+
+/// Sums the known values, or returns `None` if none are known
+fn sum_known(values: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+    values
+        .flatten()
+        .fold(None, |acc, value| Some(acc.unwrap_or(0.0) + value))
+}
+
+pub const SCHEDULE_DIR_NAME: &str = "schedules";
+
+fn schedule_filename(dir: &Path, date: NaiveDate) -> PathBuf {
+    dir.join(format!("schedule_{}.json", date.format("%Y-%m-%d")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{
+        ActuatorConfig, Config, Days, GpioLineConfig, Limit, OutputConfig, Period, RelativeLimit,
+        Season, Strategy, Tariff, TimeRange, TransferFee,
+    };
+    use crate::overrides::{Action, TimedOverride, TimedOverrides};
+    use crate::prices::{Price, PriceArea};
+    use crate::schedule::{PinSchedule, Schedule, ScheduleConfig};
+    use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+    use lazy_static::lazy_static;
+
+    const DEFAULT_CONFIG: ScheduleConfig = ScheduleConfig {
+        name: String::new(),
+        output: OutputConfig {
+            pin: Some(0),
+            actuator: ActuatorConfig::Gpio(GpioLineConfig {
+                chip: None,
+                active_low: false,
+                bias: None,
+                drive: None,
+            }),
+        },
+        outputs: Vec::new(),
+        low_limit: None,
+        high_limit: None,
+        min_on_hours: 1,
+        max_on_hours: 1,
+        min_consecutive_on_hours: None,
+        max_consecutive_off_hours: None,
+        min_consecutive_off_hours: None,
+        max_switches_per_day: None,
+        contiguous_hours: None,
+        strategy: None,
+        periods: Vec::new(),
+        allowed_hours: Vec::new(),
+        forced_on: Vec::new(),
+        forced_off: Vec::new(),
+        rolling_window: false,
+        power_kw: None,
+        required_kwh: None,
+        priority: 0,
+    };
+
+    lazy_static! {
+        static ref TZ: FixedOffset = FixedOffset::east_opt(2 * 3600).unwrap();
+        static ref TODAY: NaiveDate = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap();
+    }
+
+    fn hour_dt(hour: u32) -> DateTime<FixedOffset> {
+        TZ.from_local_datetime(&NaiveDateTime::new(
+            *TODAY,
+            NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+        ))
+        .unwrap()
+    }
+
+    fn make_hourly_prices(price: f64) -> Vec<Price> {
+        (0..=23)
+            .map(|hour| Price {
+                validity: hour_dt(hour),
+                price,
+            })
+            .collect()
+    }
+
+    fn quarter_dt(hour: u32, quarter: u32) -> DateTime<FixedOffset> {
+        TZ.from_local_datetime(&NaiveDateTime::new(
+            *TODAY,
+            NaiveTime::from_hms_opt(hour, 15 * quarter, 0).unwrap(),
+        ))
+        .unwrap()
+    }
+
+    fn make_quarterly_prices(price: f64) -> Vec<Price> {
+        (0..=23)
+            .flat_map(|hour| (0..=3).map(move |quarter| (hour, quarter)))
+            .map(|(hour, quarter)| Price {
+                validity: quarter_dt(hour, quarter),
+                price,
+            })
+            .collect()
+    }
+
+    fn config(schedules: Vec<ScheduleConfig>) -> Config {
+        Config {
+            schedules,
+            email: None,
+            tariff: None,
+            price_area: PriceArea::Fi,
+            vat_percent: None,
+            price_sources: Vec::new(),
+            price_tolerance: None,
+            simulated_gpio: None,
+            stagger_secs: None,
+            max_load_kw: None,
+        }
+    }
+
+    #[test]
+    fn test_basic_hourly() {
+        let prices = make_hourly_prices(0.0);
+
+        let schedule = PinSchedule::compute(&DEFAULT_CONFIG, &prices);
+        assert_eq!(schedule.slot_minutes, 60);
+        assert_eq!(schedule.on_slots, vec![hour_dt(0)]);
+    }
+
+    #[test]
+    fn test_basic_quarterly() {
+        let prices = make_quarterly_prices(0.0);
+
+        let schedule = PinSchedule::compute(&DEFAULT_CONFIG, &prices);
+        assert_eq!(schedule.slot_minutes, 15);
+        assert_eq!(
+            schedule.on_slots,
+            vec![
+                quarter_dt(0, 0),
+                quarter_dt(0, 1),
+                quarter_dt(0, 2),
+                quarter_dt(0, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_low_limit_hourly() {
+        let config = ScheduleConfig {
+            low_limit: Some(0.0.into()),
+            max_on_hours: 3,
+            ..DEFAULT_CONFIG
+        };
+        let prices = make_hourly_prices(0.0);
+
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(schedule.on_slots, vec![hour_dt(0), hour_dt(1), hour_dt(2)]);
+    }
+
+    #[test]
+    fn test_low_limit_quarterly() {
+        let config = ScheduleConfig {
+            low_limit: Some(0.0.into()),
+            max_on_hours: 3,
+            ..DEFAULT_CONFIG
+        };
+        let prices = make_quarterly_prices(0.0);
+
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(schedule.on_slots.len(), 12);
+        assert_eq!(schedule.on_slots.first(), Some(&quarter_dt(0, 0)));
+        assert_eq!(schedule.on_slots.last(), Some(&quarter_dt(2, 3)));
+    }
+
+    #[test]
+    fn test_takes_lowest_prices_under_low_limit_hourly() {
+        let config = ScheduleConfig {
+            min_on_hours: 0,
+            max_on_hours: 2,
+            low_limit: Some(2.0.into()),
+            ..DEFAULT_CONFIG
+        };
+        let mut prices = vec![
+            Price {
+                validity: hour_dt(0),
+                price: 1.5,
+            },
+            Price {
+                validity: hour_dt(1),
+                price: -1.0,
+            },
+            Price {
+                validity: hour_dt(2),
+                price: 1.0,
+            },
+            Price {
+                validity: hour_dt(3),
+                price: -2.0,
+            },
+        ];
+        prices.extend(make_hourly_prices(5.0).iter().skip(4));
+
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(schedule.on_slots, vec![hour_dt(1), hour_dt(3)]);
+    }
+
+    #[test]
+    fn test_takes_lowest_prices_under_low_limit_quarterly() {
+        let config = ScheduleConfig {
+            min_on_hours: 0,
+            max_on_hours: 1,
+            low_limit: Some(2.0.into()),
+            ..DEFAULT_CONFIG
+        };
+        let mut prices = vec![
+            // Hour 0
+            Price {
+                validity: quarter_dt(0, 0),
+                price: 1.5,
+            },
+            Price {
+                validity: quarter_dt(0, 1),
+                price: 1.0,
+            },
+            Price {
+                validity: quarter_dt(0, 2),
+                price: 1.5,
+            },
+            Price {
+                validity: quarter_dt(0, 3),
+                price: 2.0,
+            },
+            // Hour 1
+            Price {
+                validity: quarter_dt(1, 0),
+                price: -1.0,
+            },
+            Price {
+                validity: quarter_dt(1, 1),
+                price: -1.0,
+            },
+            Price {
+                validity: quarter_dt(1, 2),
+                price: 1.0,
+            },
+            Price {
+                validity: quarter_dt(1, 3),
+                price: -3.0,
+            },
+            // Hour 2
+            Price {
+                validity: quarter_dt(2, 0),
+                price: 1.0,
+            },
+            Price {
+                validity: quarter_dt(2, 1),
+                price: 2.0,
+            },
+            Price {
+                validity: quarter_dt(2, 2),
+                price: 1.0,
+            },
+            Price {
+                validity: quarter_dt(2, 3),
+                price: 0.0,
+            },
+            // Hour 3
+            Price {
+                validity: quarter_dt(3, 0),
+                price: 1.0,
+            },
+            Price {
+                validity: quarter_dt(3, 1),
+                price: -1.0,
+            },
+            Price {
+                validity: quarter_dt(3, 2),
+                price: 1.0,
+            },
+            Price {
+                validity: quarter_dt(3, 3),
+                price: -1.0,
+            },
+        ];
+        prices.extend(make_quarterly_prices(5.0).iter().skip(4 * 4));
+
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(
+            schedule.on_slots,
+            vec![
+                quarter_dt(1, 0),
+                quarter_dt(1, 1),
+                quarter_dt(1, 3),
+                quarter_dt(3, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn does_not_include_too_short_ranges() {
+        let config = ScheduleConfig {
+            low_limit: Some(0.0.into()),
+            high_limit: Some(1.0.into()),
+            min_on_hours: 3,
+            min_consecutive_on_hours: Some(2),
+            ..DEFAULT_CONFIG
+        };
+
+        let mut prices = vec![
+            Price {
+                validity: hour_dt(0),
+                price: 5.0,
+            },
+            // Too short, not kept --->
+            Price {
+                validity: hour_dt(1),
+                price: 0.5,
+            },
+            // <---
+            Price {
+                validity: hour_dt(2),
+                price: 5.0,
+            },
+            // Long enough, kept --->
+            Price {
+                validity: hour_dt(3),
+                price: 0.5,
+            },
+            Price {
+                validity: hour_dt(4),
+                price: 0.5,
+            },
+            // <---
+        ];
+        prices.extend(make_hourly_prices(5.0).iter().skip(5));
+
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(schedule.on_slots, vec![hour_dt(3), hour_dt(4)]);
+    }
+
+    #[test]
+    fn test_is_on_quarterly() {
+        let config = ScheduleConfig {
+            min_on_hours: 1,
+            max_on_hours: 1,
+            ..DEFAULT_CONFIG
+        };
+        let mut prices = make_quarterly_prices(5.0);
+        prices[4 * 10 + 2].price = 0.0;
+
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert!(!schedule.is_on(&quarter_dt(10, 1)));
+        assert!(schedule.is_on(&quarter_dt(10, 2)));
+        assert!(schedule.is_on(&(quarter_dt(10, 2) + Duration::minutes(14))));
+        assert_eq!(schedule.on_slots.len(), 4);
+        assert!(schedule.avg_price(&prices, true) < schedule.avg_price(&prices, false));
+    }
+
+    #[test]
+    fn test_loads_hourly_schedule() {
+        let schedule: PinSchedule = serde_json::from_str(
+            r#"{"name": "", "pin": 17, "on_hours": ["2021-01-01T10:00:00+02:00"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            schedule.outputs,
+            vec![OutputConfig {
+                pin: Some(17),
+                ..Default::default()
+            }]
+        );
+        assert_eq!(schedule.slot_minutes, 60);
+        assert_eq!(schedule.on_slots, vec![hour_dt(10)]);
+        assert!(schedule.is_on(&quarter_dt(10, 3)));
+        assert!(!schedule.is_on(&hour_dt(11)));
+
+        assert!(serde_json::from_str::<PinSchedule>(
+            r#"{"name": "", "pin": 0, "slot_minutes": 0, "on_slots": []}"#,
+        )
+        .is_err());
+
+        let json = serde_json::to_string(&schedule).unwrap();
+        let loaded: PinSchedule = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.outputs, schedule.outputs);
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let config = config(vec![ScheduleConfig {
+            min_on_hours: 2,
+            max_on_hours: 2,
+            rolling_window: true,
+            ..DEFAULT_CONFIG
+        }]);
+        let mut today = Schedule::compute(&config, &make_hourly_prices(1.0));
+        today.pins[0].on_slots = vec![hour_dt(18)];
+        // Manually turned on later today
+        today.pins[0].set_slot(hour_dt(22), true, "test", hour_dt(12));
+
+        let mut tomorrow_prices = make_hourly_prices(5.0)
+            .into_iter()
+            .map(|price| Price {
+                validity: price.validity + Duration::days(1),
+                ..price
+            })
+            .collect::<Vec<_>>();
+        tomorrow_prices[0].price = 0.0;
+        tomorrow_prices[1].price = 0.0;
+        let tomorrow = Schedule::compute(&config, &tomorrow_prices);
+
+        // The cheapest hours of tomorrow would make the 24 hours since today's hour 18 go over
+        // max_on_hours, so the hours are taken as soon as the ones of today have passed
+        let mut days = [today, tomorrow];
+        Schedule::compute_rolling(&config, None, &mut days, &hour_dt(20));
+        let [today, tomorrow] = days;
+        assert_eq!(today.pins[0].overrides.len(), 1);
+        assert_eq!(
+            today.pins[0].effective_on_slots(),
+            vec![hour_dt(18), hour_dt(22)]
+        );
+        assert_eq!(
+            tomorrow.pins[0].on_slots,
+            vec![
+                hour_dt(18) + Duration::days(1),
+                hour_dt(22) + Duration::days(1)
+            ]
+        );
+
+        let on_slots = [today, tomorrow]
+            .iter()
+            .flat_map(|schedule| schedule.pins[0].effective_on_slots())
+            .collect::<Vec<_>>();
+        for hour in 1..=48 {
+            let end = hour_dt(0) + Duration::hours(hour);
+            let on_hours = on_slots
+                .iter()
+                .filter(|slot| end - Duration::hours(24) <= **slot && **slot < end)
+                .count();
+            assert!(on_hours <= 2, "{} hours on before {}", on_hours, end);
+            if hour >= 24 {
+                assert_eq!(on_hours, 2, "{} hours on before {}", on_hours, end);
+            }
+        }
+    }
+
+    #[test]
+    fn test_recompute_keeps_past_slots() {
+        let mut prices = make_hourly_prices(1.0);
+        for hour in [5, 6, 7] {
+            prices[hour].price = 0.0;
+        }
+        let mut config = config(vec![ScheduleConfig {
+            min_on_hours: 2,
+            max_on_hours: 2,
+            ..DEFAULT_CONFIG
+        }]);
+        let mut schedule = Schedule::compute(&config, &prices);
+        schedule.pins[0].on_slots = vec![hour_dt(1), hour_dt(2), hour_dt(10)];
+
+        schedule.pins[0].set_slot(hour_dt(12), true, "test", hour_dt(0));
+
+        // The current slot is kept too
+        config.schedules[0].min_on_hours = 3;
+        config.schedules[0].max_on_hours = 3;
+        let now = hour_dt(2) + Duration::minutes(30);
+        let schedule = &Schedule::recompute(&config, None, &[schedule], &now)[0];
+        assert_eq!(
+            schedule.pins[0].on_slots,
+            vec![hour_dt(1), hour_dt(2), hour_dt(5), hour_dt(6), hour_dt(7)]
+        );
+        assert_eq!(
+            schedule.pins[0].effective_on_slots(),
+            vec![
+                hour_dt(1),
+                hour_dt(2),
+                hour_dt(5),
+                hour_dt(6),
+                hour_dt(7),
+                hour_dt(12)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recompute_rolling() {
+        let mut config = config(vec![ScheduleConfig {
+            min_on_hours: 1,
+            max_on_hours: 1,
+            ..DEFAULT_CONFIG
+        }]);
+        let mut today = Schedule::compute(&config, &make_hourly_prices(1.0));
+        today.pins[0].on_slots = vec![hour_dt(18)];
+        let mut tomorrow_prices = make_hourly_prices(5.0)
+            .into_iter()
+            .map(|price| Price {
+                validity: price.validity + Duration::days(1),
+                ..price
+            })
+            .collect::<Vec<_>>();
+        tomorrow_prices[3].price = 0.0;
+        let tomorrow = Schedule::compute(&config, &tomorrow_prices);
+
+        // Recomputing the days separately would also turn on tomorrow's hour 0, within 24 hours
+        // of today's hour 18
+        config.schedules[0].min_on_hours = 2;
+        config.schedules[0].max_on_hours = 2;
+        config.schedules[0].rolling_window = true;
+        let days = Schedule::recompute(&config, None, &[today, tomorrow], &hour_dt(20));
+        assert_eq!(days[0].pins[0].on_slots, vec![hour_dt(18)]);
+        assert_eq!(
+            days[1].pins[0].on_slots,
+            vec![
+                hour_dt(3) + Duration::days(1),
+                hour_dt(18) + Duration::days(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut schedule = PinSchedule::compute(&DEFAULT_CONFIG, &make_hourly_prices(1.0));
+        assert_eq!(schedule.on_slots, vec![hour_dt(0)]);
+
+        schedule.set_slot(hour_dt(0), false, "test", hour_dt(0));
+        schedule.set_slot(hour_dt(5), true, "test", hour_dt(0));
+        assert!(!schedule.is_on(&hour_dt(0)));
+        assert!(schedule.is_on(&(hour_dt(5) + Duration::minutes(30))));
+        assert_eq!(schedule.on_slots, vec![hour_dt(0)]);
+        assert_eq!(schedule.effective_on_slots(), vec![hour_dt(5)]);
+
+        // Setting a slot back to the computed state removes the override
+        schedule.set_slot(hour_dt(0), true, "test", hour_dt(0));
+        assert_eq!(schedule.overrides.len(), 1);
+        assert_eq!(schedule.effective_on_slots(), vec![hour_dt(0), hour_dt(5)]);
+    }
+
+    #[test]
+    fn test_timed_overrides() {
+        let config = config(vec![ScheduleConfig {
+            name: "Boiler".to_string(),
+            ..DEFAULT_CONFIG
+        }]);
+        let mut schedule = Schedule::compute(&config, &make_hourly_prices(1.0));
+        schedule.pins[0].set_slot(hour_dt(21), false, "test", hour_dt(0));
+        assert_eq!(schedule.pins[0].effective_on_slots(), vec![hour_dt(0)]);
+
+        let timed_override = |name: &str, action, start, end| TimedOverride {
+            name: name.to_string(),
+            action,
+            start,
+            end,
+            author: "cli".to_string(),
+        };
+        let mut timed_overrides = TimedOverrides::default();
+        timed_overrides.add(timed_override(
+            "Boiler",
+            Action::Boost,
+            hour_dt(20) + Duration::minutes(30),
+            hour_dt(22),
+        ));
+        timed_overrides.add(timed_override(
+            "Heater",
+            Action::Boost,
+            hour_dt(5),
+            hour_dt(6),
+        ));
+        let boosted = schedule.with_timed_overrides(&timed_overrides);
+        assert_eq!(
+            boosted.pins[0].effective_on_slots(),
+            vec![hour_dt(0), hour_dt(20), hour_dt(21)]
+        );
+        assert!(boosted.pins[0].is_on(&(hour_dt(21) + Duration::minutes(59))));
+        assert_eq!(
+            boosted.pins[0].override_for(&hour_dt(20)).unwrap().author,
+            "cli"
+        );
+
+        let mut timed_overrides = TimedOverrides::default();
+        timed_overrides.add(timed_override(
+            "Boiler",
+            Action::Pause,
+            hour_dt(0) - Duration::hours(1),
+            hour_dt(0) + Duration::minutes(30),
+        ));
+        let paused = schedule.with_timed_overrides(&timed_overrides);
+        assert!(paused.pins[0].effective_on_slots().is_empty());
+        // The schedule itself is not changed
+        assert_eq!(schedule.pins[0].effective_on_slots(), vec![hour_dt(0)]);
+    }
+
+    #[test]
+    fn test_required_energy() {
+        let config = ScheduleConfig {
+            min_on_hours: 0,
+            max_on_hours: 0,
+            power_kw: Some(2.0),
+            required_kwh: Some(5.0),
+            ..DEFAULT_CONFIG
+        };
+        let prices = make_quarterly_prices(10.0);
+
+        // 5 kWh with 2 kW takes 2.5 hours, i.e. 10 quarters
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(schedule.on_slots.len(), 10);
+        assert_eq!(schedule.energy_kwh(), Some(5.0));
+        // 5 kWh at 10 c/kWh
+        assert!((schedule.cost_eur(&prices).unwrap() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_max_consecutive_off_hours() {
+        let config = ScheduleConfig {
+            high_limit: Some(1.5.into()),
+            max_consecutive_off_hours: Some(4),
+            ..DEFAULT_CONFIG
+        };
+        let mut prices = make_hourly_prices(5.0);
+        prices[0].price = 1.0;
+        for hour in [3, 8, 13, 18, 22] {
+            prices[hour].price = 2.0;
+        }
+
+        // The cheapest hours that break the day into at most 4 hour gaps are used even
+        // though they are over `high_limit`
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(
+            schedule.on_slots,
+            vec![
+                hour_dt(0),
+                hour_dt(3),
+                hour_dt(8),
+                hour_dt(13),
+                hour_dt(18),
+                hour_dt(22)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_consecutive_off_hours_quarterly() {
+        let config = ScheduleConfig {
+            min_on_hours: 0,
+            max_on_hours: 0,
+            max_consecutive_off_hours: Some(1),
+            ..DEFAULT_CONFIG
+        };
+        let prices = make_quarterly_prices(5.0)
+            .into_iter()
+            .enumerate()
+            .map(|(i, price)| Price {
+                price: (i % 7) as f64,
+                ..price
+            })
+            .collect::<Vec<_>>();
+
+        // No more than 4 quarters off in a row, including the start and end of the day
+        let schedule = PinSchedule::compute(&config, &prices);
+        let mut last_on = quarter_dt(0, 0) - Duration::minutes(15);
+        for slot in schedule
+            .on_slots
+            .iter()
+            .chain([&(quarter_dt(23, 3) + Duration::minutes(15))])
+        {
+            assert!(*slot - last_on <= Duration::minutes(5 * 15));
+            last_on = *slot;
+        }
+    }
+
+    #[test]
+    fn test_allowed_and_forced_hours() {
+        let config = ScheduleConfig {
+            min_on_hours: 3,
+            max_on_hours: 3,
+            allowed_hours: vec![TimeRange {
+                start: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                days: Days::All,
+            }],
+            forced_on: vec![TimeRange {
+                start: NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+                days: Days::All,
+            }],
+            forced_off: vec![TimeRange {
+                start: NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(19, 0, 0).unwrap(),
+                days: Days::All,
+            }],
+            ..DEFAULT_CONFIG
+        };
+        let prices = make_hourly_prices(5.0)
+            .into_iter()
+            .enumerate()
+            .map(|(hour, price)| Price {
+                price: 24.0 - hour as f64,
+                ..price
+            })
+            .collect::<Vec<_>>();
+
+        // Forced hour counts towards the total, the rest are the cheapest allowed hours
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(
+            schedule.on_slots,
+            vec![hour_dt(5), hour_dt(20), hour_dt(21)]
+        );
+    }
+
+    #[test]
+    fn test_weekday_ranges() {
+        // TODAY is a Friday
+        let range = |days| TimeRange {
+            start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+            days,
+        };
+        assert!(range(Days::Weekdays).contains(&hour_dt(23)));
+        assert!(range(Days::Weekdays).contains(&hour_dt(1)));
+        assert!(!range(Days::Weekdays).contains(&hour_dt(2)));
+        assert!(!range(Days::Weekends).contains(&hour_dt(23)));
+        assert!(range(Days::Weekends).contains(&(hour_dt(23) + Duration::days(1))));
+
+        let config = ScheduleConfig {
+            min_on_hours: 0,
+            max_on_hours: 0,
+            forced_on: vec![range(Days::Weekends)],
+            ..DEFAULT_CONFIG
+        };
+        let schedule = PinSchedule::compute(&config, &make_hourly_prices(0.0));
+        assert!(schedule.on_slots.is_empty());
+    }
+
+    #[test]
+    fn test_periods() {
+        let period = |start, end, min_on_hours| Period {
+            range: TimeRange {
+                start: NaiveTime::from_hms_opt(start, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(end, 0, 0).unwrap(),
+                days: Days::All,
+            },
+            min_on_hours,
+        };
+        let config = ScheduleConfig {
+            min_on_hours: 6,
+            max_on_hours: 6,
+            periods: vec![period(0, 8, 1), period(8, 16, 2), period(16, 0, 2)],
+            ..DEFAULT_CONFIG
+        };
+        // Cheapest at night, more expensive towards the evening
+        let prices = make_hourly_prices(0.0)
+            .into_iter()
+            .enumerate()
+            .map(|(hour, price)| Price {
+                price: hour as f64,
+                ..price
+            })
+            .collect::<Vec<_>>();
+
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(
+            schedule.on_slots,
+            vec![
+                hour_dt(0),
+                hour_dt(1),
+                hour_dt(8),
+                hour_dt(9),
+                hour_dt(16),
+                hour_dt(17)
+            ]
+        );
+
+        // Slots under `low_limit` already use up `max_on_hours`
+        let config = ScheduleConfig {
+            low_limit: Some(5.0.into()),
+            min_on_hours: 3,
+            max_on_hours: 3,
+            periods: vec![period(16, 0, 1)],
+            ..DEFAULT_CONFIG
+        };
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(schedule.on_slots, vec![hour_dt(0), hour_dt(1), hour_dt(2)]);
+    }
+
+    #[test]
+    fn test_max_switches_per_day() {
+        let config = ScheduleConfig {
+            min_on_hours: 3,
+            max_on_hours: 3,
+            max_switches_per_day: Some(2),
+            ..DEFAULT_CONFIG
+        };
+        let mut prices = make_hourly_prices(5.0);
+        for hour in [2, 5, 8] {
+            prices[hour].price = 0.0;
+        }
+        prices[3].price = 1.0;
+        prices[4].price = 1.0;
+
+        // Cheapest to fill the gap between hours 2 and 5, and then drop hour 8
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(
+            schedule.on_slots,
+            vec![hour_dt(2), hour_dt(3), hour_dt(4), hour_dt(5)]
+        );
+    }
+
+    #[test]
+    fn test_min_consecutive_off_hours() {
+        let config = ScheduleConfig {
+            min_on_hours: 3,
+            max_on_hours: 3,
+            min_consecutive_off_hours: Some(2),
+            ..DEFAULT_CONFIG
+        };
+        let mut prices = make_hourly_prices(5.0);
+        for hour in [2, 4, 10] {
+            prices[hour].price = 0.0;
+        }
+        prices[3].price = 1.0;
+
+        // Gap at hour 3 is too short and cheaper to fill than dropping either run
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(
+            schedule.on_slots,
+            vec![hour_dt(2), hour_dt(3), hour_dt(4), hour_dt(10)]
+        );
+    }
+
+    #[test]
+    fn test_contiguous_block() {
+        let config = ScheduleConfig {
+            contiguous_hours: Some(3),
+            ..DEFAULT_CONFIG
+        };
+        let mut prices = make_hourly_prices(5.0);
+        // Cheapest single hour is not part of the cheapest block
+        prices[2].price = 0.0;
+        for hour in [10, 11, 12] {
+            prices[hour].price = 2.0;
+        }
+
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(
+            schedule.on_slots,
+            vec![hour_dt(10), hour_dt(11), hour_dt(12)]
+        );
+
+        let config = ScheduleConfig {
+            allowed_hours: vec![TimeRange {
+                start: NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+                days: Days::All,
+            }],
+            ..config
+        };
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(schedule.on_slots, vec![hour_dt(0), hour_dt(1), hour_dt(2)]);
+    }
+
+    #[test]
+    fn test_cheapest_strategy() {
+        let config = ScheduleConfig {
+            strategy: Some(Strategy::Cheapest),
+            high_limit: Some(1.0.into()),
+            min_on_hours: 2,
+            max_on_hours: 0,
+            ..DEFAULT_CONFIG
+        };
+        let mut prices = make_hourly_prices(5.0);
+        prices[7].price = 3.0;
+        prices[19].price = 2.0;
+
+        // Limits are ignored
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(schedule.on_slots, vec![hour_dt(7), hour_dt(19)]);
+    }
+
+    #[test]
+    fn test_threshold_strategy() {
+        let config = ScheduleConfig {
+            strategy: Some(Strategy::Threshold),
+            low_limit: Some(2.0.into()),
+            min_on_hours: 5,
+            max_on_hours: 1,
+            ..DEFAULT_CONFIG
+        };
+        let mut prices = make_hourly_prices(5.0);
+        prices[3].price = 1.0;
+        prices[4].price = 2.0;
+        prices[5].price = 2.5;
+        prices[6].price = -1.0;
+
+        // On-hour settings are ignored
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(schedule.on_slots, vec![hour_dt(3), hour_dt(4), hour_dt(6)]);
+    }
+
+    #[test]
+    fn test_relative_limits() {
+        let prices = (1..=5).map(f64::from).collect::<Vec<_>>();
+        assert_eq!(
+            Limit::Relative(RelativeLimit::Percentile(25.0)).resolve(&prices),
+            2.0
+        );
+        assert_eq!(
+            Limit::Relative(RelativeLimit::Percentile(90.0)).resolve(&prices),
+            4.6
+        );
+        assert_eq!(
+            Limit::Relative(RelativeLimit::AveragePercent(-20.0)).resolve(&prices),
+            2.4
+        );
+        assert_eq!(
+            Limit::Relative(RelativeLimit::AveragePercent(-20.0)).resolve(&[-1.0]),
+            -1.2
+        );
+
+        let config: ScheduleConfig = serde_json::from_str(
+            r#"{
+                "name": "",
+                "pin": 0,
+                "low_limit": {"percentile": 25},
+                "high_limit": 10.0,
+                "min_on_hours": 0,
+                "max_on_hours": 24
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.low_limit,
+            Some(Limit::Relative(RelativeLimit::Percentile(25.0)))
+        );
+        assert_eq!(config.high_limit, Some(Limit::Absolute(10.0)));
+
+        // Cheapest quarter of the day
+        let prices = make_hourly_prices(0.0)
+            .into_iter()
+            .enumerate()
+            .map(|(hour, price)| Price {
+                price: ((hour * 7) % 24) as f64,
+                ..price
+            })
+            .collect::<Vec<_>>();
+        let schedule = PinSchedule::compute(&config, &prices);
+        assert_eq!(schedule.on_slots.len(), 6);
+        assert_eq!(schedule.avg_price(&prices, true), 2.5);
+    }
+
+    #[test]
+    fn test_tariff() {
+        let tariff = Tariff {
+            margin: 0.5,
+            electricity_tax: 2.0,
+            transfer: vec![
+                TransferFee {
+                    price: 3.0,
+                    hours: vec![TimeRange {
+                        start: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+                        end: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                        days: Days::All,
+                    }],
+                },
+                TransferFee {
+                    price: 1.0,
+                    hours: Vec::new(),
+                },
+            ],
+            seasons: vec![Season {
+                months: vec![6, 7, 8],
+                transfer: vec![TransferFee {
+                    price: 2.0,
+                    hours: Vec::new(),
+                }],
+            }],
+        };
+        assert_eq!(tariff.costs_at(&hour_dt(12)), 5.5);
+        assert_eq!(tariff.costs_at(&hour_dt(23)), 3.5);
+        assert_eq!(tariff.costs_at(&(hour_dt(12) + Duration::days(180))), 4.5);
+
+        // Day transfer makes the cheapest spot price more expensive than night
+        let mut prices = make_hourly_prices(3.0);
+        prices[12].price = 1.0;
+        let config = Config {
+            tariff: Some(tariff),
+            ..config(vec![DEFAULT_CONFIG])
+        };
+        let schedule = Schedule::compute(&config, &prices);
+        assert_eq!(schedule.pins[0].on_slots, vec![hour_dt(0)]);
+        assert_eq!(schedule.prices, prices);
+        assert_eq!(
+            schedule.avg_price_for_slot(hour_dt(0), Duration::hours(1)),
+            Some(6.5)
+        );
+    }
+}
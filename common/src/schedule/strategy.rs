@@ -0,0 +1,294 @@
+use chrono::{DateTime, Duration, FixedOffset};
+use std::ops::Range;
+
+use crate::config::{ScheduleConfig, Strategy};
+use crate::prices::Price;
+
+/// Prices of the time window being scheduled
+pub struct Window<'a> {
+    /// Prices in time order, one per slot
+    pub prices: &'a [Price],
+
+    /// Length of one slot
+    pub slot: Duration,
+
+    /// Length of the whole window, normally 24 hours
+    pub length: Duration,
+
+    /// `low_limit` of the config resolved against the prices of the window
+    pub low_limit: Option<f64>,
+
+    /// `high_limit` of the config resolved against the prices of the window
+    pub high_limit: Option<f64>,
+}
+
+impl<'a> Window<'a> {
+    pub fn new(
+        config: &ScheduleConfig,
+        prices: &'a [Price],
+        slot: Duration,
+        length: Duration,
+    ) -> Self {
+        let values = prices.iter().map(|price| price.price).collect::<Vec<_>>();
+        Self {
+            prices,
+            slot,
+            length,
+            low_limit: config.low_limit.map(|limit| limit.resolve(&values)),
+            high_limit: config.high_limit.map(|limit| limit.resolve(&values)),
+        }
+    }
+
+    /// Length of the window in days
+    pub fn days(&self) -> f64 {
+        self.length.num_minutes() as f64 / (24.0 * 60.0)
+    }
+
+    /// Number of slots in the given number of hours
+    pub fn hours_to_slots(&self, hours: u32) -> usize {
+        (i64::from(hours) * 60 / self.slot.num_minutes()) as usize
+    }
+
+    /// Number of slots in the window for the given number of hours per day
+    pub fn daily_hours_to_slots(&self, hours: u32) -> usize {
+        (f64::from(hours) * self.days() * 60.0 / self.slot.num_minutes() as f64).round() as usize
+    }
+
+    /// Number of slots needed to satisfy `min_on_hours`, or `required_kwh` if set
+    pub fn min_on_slots(&self, config: &ScheduleConfig) -> usize {
+        match (config.required_kwh, config.power_kw) {
+            (Some(kwh), Some(kw)) if kw > 0.0 => {
+                let slot_hours = self.slot.num_minutes() as f64 / 60.0;
+                (kwh * self.days() / (kw * slot_hours)).ceil() as usize
+            }
+            _ => self.daily_hours_to_slots(config.min_on_hours),
+        }
+    }
+}
+
+/// Chooses the slots when the switch is on. `allowed_hours`, `forced_on`, `forced_off` and the
+/// constraints on consecutive hours and switching are applied on top of the result, but a
+/// strategy may take them into account to make better choices.
+pub trait SchedulingStrategy {
+    /// Returns whether each slot of the window is on
+    fn select(&self, config: &ScheduleConfig, window: &Window) -> Vec<bool>;
+}
+
+pub(crate) fn strategy_for(strategy: Strategy) -> &'static dyn SchedulingStrategy {
+    match strategy {
+        Strategy::Limits => &LimitsStrategy,
+        Strategy::Contiguous => &ContiguousStrategy,
+        Strategy::Cheapest => &CheapestStrategy,
+        Strategy::Threshold => &ThresholdStrategy,
+    }
+}
+
+/// Always on under `low_limit` up to `max_on_hours`, never on over `high_limit`, and on for at
+/// least `min_on_hours` at the cheapest prices in between
+pub struct LimitsStrategy;
+
+impl SchedulingStrategy for LimitsStrategy {
+    fn select(&self, config: &ScheduleConfig, window: &Window) -> Vec<bool> {
+        let first_slot = window.prices.first().map(|price| price.validity);
+        let last_slot = window.prices.last().map(|price| price.validity);
+
+        // Forced slots are always on, and other slots are candidates only if allowed
+        let (forced_on, mut prices): (Vec<Price>, Vec<Price>) = window
+            .prices
+            .iter()
+            .partition(|price| config.is_forced_on(&price.validity));
+        prices.retain(|price| config.is_allowed(&price.validity));
+
+        // Filter out prices over `high_limit`
+        prices.retain(|price| {
+            if let Some(limit) = window.high_limit {
+                price.price < limit
+            } else {
+                true
+            }
+        });
+
+        // Sort by price
+        prices.sort_by(|a, b| a.price.partial_cmp(&b.price).unwrap());
+
+        // Take all prices less than or equal to `low_limit`
+        let (mut result, mut others): (Vec<Price>, Vec<Price>) =
+            prices.iter().copied().partition(|price| {
+                if let Some(limit) = window.low_limit {
+                    price.price <= limit
+                } else {
+                    false
+                }
+            });
+
+        // Truncate to `max_on_hours`, including forced slots
+        let max_on_slots = window
+            .daily_hours_to_slots(config.max_on_hours)
+            .saturating_sub(forced_on.len());
+        if result.len() > max_on_slots {
+            result.truncate(max_on_slots);
+        }
+        result.extend(&forced_on);
+
+        // Take the cheapest slots of each period up to the period's `min_on_hours`, without going
+        // over `max_on_hours`
+        let max_total_slots = window
+            .daily_hours_to_slots(config.max_on_hours)
+            .max(forced_on.len());
+        for period in &config.periods {
+            let already_on = result
+                .iter()
+                .filter(|price| period.range.contains(&price.validity))
+                .count();
+            let mut missing = window
+                .daily_hours_to_slots(period.min_on_hours)
+                .saturating_sub(already_on)
+                .min(max_total_slots.saturating_sub(result.len()));
+            others.retain(|price| {
+                if missing > 0 && period.range.contains(&price.validity) {
+                    result.push(*price);
+                    missing -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        // Fill up to `min_on_hours`, or up to the slots needed for `required_kwh`
+        let min_on_slots = window.min_on_slots(config);
+        if result.len() < min_on_slots {
+            others.sort_by(|a, b| a.price.partial_cmp(&b.price).unwrap());
+            result.extend(others.iter().take(min_on_slots - result.len()));
+        }
+
+        // Sort back to time order
+        result.sort_by_key(|price| price.validity);
+
+        // Remove ranges that occur in the middle of the day and are shorter than `min_consecutive_on_hours`
+        if let Some(min_consecutive_on_hours) = config.min_consecutive_on_hours {
+            let slot = window.slot;
+            let mut ranges: Vec<(DateTime<FixedOffset>, DateTime<FixedOffset>)> = Vec::new();
+            for (i, price) in result.iter().enumerate() {
+                if i == 0 {
+                    ranges.push((price.validity, price.validity));
+                } else {
+                    let (_, end) = ranges.last_mut().unwrap();
+                    if price.validity == *end + slot {
+                        *end = price.validity;
+                    } else {
+                        ranges.push((price.validity, price.validity));
+                    }
+                }
+            }
+            let too_short = ranges
+                .into_iter()
+                .filter(|(start, end)| {
+                    Some(*start) != first_slot
+                        && Some(*end) != last_slot
+                        // + slot because we use starts of slots
+                        && *end - *start + slot < Duration::hours(min_consecutive_on_hours.into())
+                })
+                .collect::<Vec<_>>();
+
+            result.retain(|price| {
+                config.is_forced_on(&price.validity)
+                    || !too_short
+                        .iter()
+                        .any(|(start, end)| *start <= price.validity && price.validity <= *end)
+            });
+        }
+
+        window
+            .prices
+            .iter()
+            .map(|price| result.iter().any(|r| r.validity == price.validity))
+            .collect()
+    }
+}
+
+/// On for one uninterrupted run of `contiguous_hours` at the cheapest time within
+/// `allowed_hours`
+pub struct ContiguousStrategy;
+
+impl SchedulingStrategy for ContiguousStrategy {
+    fn select(&self, config: &ScheduleConfig, window: &Window) -> Vec<bool> {
+        let length = window.hours_to_slots(config.contiguous_hours.unwrap_or(0));
+        let block = cheapest_block(window.prices, window.slot, length, |price| {
+            config.is_allowed(&price.validity)
+        });
+        (0..window.prices.len())
+            .map(|i| block.as_ref().is_some_and(|block| block.contains(&i)))
+            .collect()
+    }
+}
+
+/// On for `min_on_hours` (or `required_kwh`) at the cheapest allowed times, ignoring the price
+/// limits
+pub struct CheapestStrategy;
+
+impl SchedulingStrategy for CheapestStrategy {
+    fn select(&self, config: &ScheduleConfig, window: &Window) -> Vec<bool> {
+        let mut candidates = (0..window.prices.len())
+            .filter(|&i| config.is_allowed(&window.prices[i].validity))
+            .collect::<Vec<_>>();
+        candidates.sort_by(|&a, &b| {
+            window.prices[a]
+                .price
+                .partial_cmp(&window.prices[b].price)
+                .unwrap()
+        });
+        candidates.truncate(window.min_on_slots(config));
+
+        let mut on = vec![false; window.prices.len()];
+        for i in candidates {
+            on[i] = true;
+        }
+        on
+    }
+}
+
+/// On whenever the price is at or under `low_limit` and under `high_limit`, with no limits on
+/// the number of hours
+pub struct ThresholdStrategy;
+
+impl SchedulingStrategy for ThresholdStrategy {
+    fn select(&self, _config: &ScheduleConfig, window: &Window) -> Vec<bool> {
+        window
+            .prices
+            .iter()
+            .map(|price| {
+                window.low_limit.is_some_and(|limit| price.price <= limit)
+                    && window.high_limit.is_none_or(|limit| price.price < limit)
+            })
+            .collect()
+    }
+}
+
+/// Finds the consecutive `length` slots with the lowest total price, where all slots are
+/// `allowed` and follow each other without gaps. `prices` must be in time order.
+fn cheapest_block(
+    prices: &[Price],
+    slot: Duration,
+    length: usize,
+    allowed: impl Fn(&Price) -> bool,
+) -> Option<Range<usize>> {
+    if length == 0 || length > prices.len() {
+        return None;
+    }
+    (0..=prices.len() - length)
+        .map(|start| start..start + length)
+        .filter(|block| {
+            let block = &prices[block.clone()];
+            block.iter().all(&allowed)
+                && block
+                    .windows(2)
+                    .all(|pair| pair[1].validity - pair[0].validity == slot)
+        })
+        .map(|block| {
+            let total = prices[block.clone()].iter().map(|p| p.price).sum::<f64>();
+            (total, block)
+        })
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+        .map(|(_, block)| block)
+}
@@ -2,7 +2,7 @@ use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, Timelike};
 
 pub trait NaiveDateExt {
     fn start_of_day(&self) -> DateTime<Local>;
-    fn iter_hours(&self) -> HourIterator;
+    fn iter_slots(&self, length: Duration) -> SlotIterator;
 }
 
 impl NaiveDateExt for NaiveDate {
@@ -12,20 +12,20 @@ impl NaiveDateExt for NaiveDate {
             .unwrap()
     }
 
-    fn iter_hours(&self) -> HourIterator {
-        HourIterator(Some(self.start_of_day()))
+    fn iter_slots(&self, length: Duration) -> SlotIterator {
+        SlotIterator(Some(self.start_of_day()), length)
     }
 }
 
-pub struct HourIterator(Option<DateTime<Local>>);
+pub struct SlotIterator(Option<DateTime<Local>>, Duration);
 
-impl Iterator for HourIterator {
+impl Iterator for SlotIterator {
     type Item = DateTime<Local>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let result = self.0;
         if let Some(cur) = self.0 {
-            let next = cur + Duration::hours(1);
+            let next = cur + self.1;
             if next.date_naive() == cur.date_naive() {
                 self.0 = Some(next);
             } else {
@@ -36,14 +36,15 @@ impl Iterator for HourIterator {
     }
 }
 
-pub trait LocalExt {
-    fn current_hour() -> DateTime<Local>;
+pub trait DateTimeExt {
+    fn start_of_slot(&self, length: Duration) -> Self;
 }
 
-impl LocalExt for Local {
-    fn current_hour() -> DateTime<Local> {
-        Self::now()
-            .with_minute(0)
+impl DateTimeExt for DateTime<Local> {
+    fn start_of_slot(&self, length: Duration) -> Self {
+        // Slots are at most one hour long and aligned to the start of the hour
+        let slot_minutes = length.num_minutes() as u32;
+        self.with_minute(self.minute() - self.minute() % slot_minutes)
             .and_then(|t| t.with_second(0))
             .and_then(|t| t.with_nanosecond(0))
             .unwrap()
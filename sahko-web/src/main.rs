@@ -7,6 +7,7 @@ use crate::routes::email::send_email_route;
 use axum::routing::get;
 use axum::routing::post;
 use axum::{Extension, Router};
+use common::paths::Paths;
 use eyre::{Context, Result};
 use std::net::SocketAddr;
 use std::str::FromStr;
@@ -17,7 +18,10 @@ use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use crate::routes::index::index_route;
-use crate::routes::schedule::update_schedule_route;
+use crate::routes::overrides::{boost_route, cancel_route, pause_route};
+use crate::routes::schedule::{
+    compute_schedule_route, recompute_schedule_route, update_schedule_route,
+};
 use crate::routes::stats::stats_route;
 
 #[tokio::main]
@@ -37,13 +41,19 @@ async fn main() -> Result<()> {
     let app = Router::new()
         .route("/", get(index_route))
         .route("/schedule", post(update_schedule_route))
+        .route("/schedule/compute", post(compute_schedule_route))
+        .route("/schedule/recompute", post(recompute_schedule_route))
         .route("/email", post(send_email_route))
+        .route("/boost", post(boost_route))
+        .route("/pause", post(pause_route))
+        .route("/cancel", post(cancel_route))
         .route("/stats", get(stats_route))
         .nest_service(
             "/assets",
             ServeDir::new(format!("{}/assets", assets_path.to_str().unwrap())).precompressed_gzip(),
         )
         .layer(Extension(write_lock))
+        .layer(Extension(Paths::default()))
         .layer(CompressionLayer::new());
 
     let bind = std::env::var("BIND").unwrap_or_else(|_| "127.0.0.1:8000".to_string());
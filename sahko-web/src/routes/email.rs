@@ -1,21 +1,28 @@
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
+use axum::Extension;
 use axum_extra::extract::Form;
 use chrono::NaiveDate;
 use common::config::Config;
 use common::email::EmailClient;
+use common::overrides::TimedOverrides;
+use common::paths::Paths;
 use common::schedule::Schedule;
 use serde::Deserialize;
 
-pub async fn send_email_route(Form(body): Form<SendEmailBody>) -> impl IntoResponse {
+pub async fn send_email_route(
+    Extension(paths): Extension<Paths>,
+    Form(body): Form<SendEmailBody>,
+) -> impl IntoResponse {
     let Ok(config) = Config::load("config.json") else {
         return StatusCode::INTERNAL_SERVER_ERROR;
     };
     let email_client = EmailClient::new(&config.email);
-    let Some(schedule) = Schedule::load_for_date(body.date) else {
+    let Some(schedule) = Schedule::load_from(&paths.schedules, body.date) else {
         return StatusCode::NOT_FOUND;
     };
-    let Ok(_) = email_client.send_schedule(body.date, &schedule) else {
+    let timed_overrides = TimedOverrides::load_from(&paths.overrides).unwrap_or_default();
+    let Ok(_) = email_client.send_schedule(body.date, &schedule, &timed_overrides) else {
         return StatusCode::INTERNAL_SERVER_ERROR;
     };
     StatusCode::NO_CONTENT
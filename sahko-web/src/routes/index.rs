@@ -1,10 +1,11 @@
-use crate::date::LocalExt;
 use crate::routes::schedule::ScheduleModel;
 use askama::Template;
-use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use axum::Extension;
 use axum_extra::extract::Query;
 use chrono::{Duration, Local, NaiveDate};
+use common::overrides::TimedOverrides;
+use common::paths::Paths;
 use common::schedule::Schedule;
 use serde::Deserialize;
 
@@ -16,32 +17,38 @@ pub struct IndexQuery {
 #[derive(Template)]
 #[template(path = "pages/index.html")]
 struct IndexTemplate {
+    date: NaiveDate,
     current_date: String,
     prev_date: Option<NaiveDate>,
     next_date: Option<NaiveDate>,
-    schedule: ScheduleModel,
+    schedule: Option<ScheduleModel>,
 }
 
-pub async fn index_route(query: Query<IndexQuery>) -> Response {
-    let current_date = query
-        .date
-        .unwrap_or_else(|| Local::now().naive_local().date());
+pub async fn index_route(Extension(paths): Extension<Paths>, query: Query<IndexQuery>) -> Response {
+    let today = Local::now().naive_local().date();
+    let current_date = query.date.unwrap_or(today);
 
-    let Some(schedule) = Schedule::load_for_date(current_date) else {
-        return (
-            StatusCode::NOT_FOUND,
-            format!("Schedule not found for {}", current_date),
-        )
-            .into_response();
-    };
+    let schedule = Schedule::load_from(&paths.schedules, current_date);
+    let timed_overrides = TimedOverrides::load_from(&paths.overrides).unwrap_or_default();
     let prev_date = current_date - Duration::days(1);
     let next_date = current_date + Duration::days(1);
 
     IndexTemplate {
+        date: current_date,
         current_date: current_date.format("%a %d.%m.%Y").to_string(),
-        prev_date: Schedule::load_for_date(prev_date).map(|_| prev_date),
-        next_date: Schedule::load_for_date(next_date).map(|_| next_date),
-        schedule: ScheduleModel::from_pin_schedules(Local::current_hour(), current_date, &schedule),
+        prev_date: Schedule::load_from(&paths.schedules, prev_date).map(|_| prev_date),
+        // Tomorrow's schedule can be computed from the page once the prices are available
+        next_date: (Schedule::load_from(&paths.schedules, next_date).is_some()
+            || next_date <= today + Duration::days(1))
+        .then_some(next_date),
+        schedule: schedule.map(|schedule| {
+            ScheduleModel::from_pin_schedules(
+                Local::now(),
+                current_date,
+                &schedule,
+                &timed_overrides,
+            )
+        }),
     }
     .into_response()
 }
@@ -1,4 +1,21 @@
 pub mod email;
 pub mod index;
+pub mod overrides;
 pub mod schedule;
 pub mod stats;
+
+use axum::http::HeaderMap;
+use std::sync::OnceLock;
+
+/// Name of the user making a change, from the header named by the `USER_HEADER` environment
+/// variable. The header is set by an authenticating reverse proxy, and isn't trusted unless
+/// configured, since anyone reaching the app directly could send it.
+pub fn author(headers: &HeaderMap) -> &str {
+    static USER_HEADER: OnceLock<Option<String>> = OnceLock::new();
+    USER_HEADER
+        .get_or_init(|| std::env::var("USER_HEADER").ok())
+        .as_deref()
+        .and_then(|name| headers.get(name))
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("web")
+}
@@ -0,0 +1,164 @@
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use axum_extra::extract::Form;
+use chrono::{Local, NaiveDate, NaiveTime};
+use common::config::Config;
+use common::email::EmailClient;
+use common::overrides::{end_time, Action, TimedOverride, TimedOverrides};
+use common::paths::Paths;
+use common::schedule::Schedule;
+use serde::{Deserialize, Deserializer};
+
+use crate::lock::WriteLock;
+use crate::routes::author;
+use crate::routes::schedule::{ScheduleModel, ScheduleTemplate};
+
+pub async fn boost_route(
+    Extension(write_lock): Extension<WriteLock>,
+    Extension(paths): Extension<Paths>,
+    headers: HeaderMap,
+    Form(body): Form<TimedOverrideBody>,
+) -> Response {
+    add_timed_override(write_lock, &paths, &headers, body, Action::Boost)
+}
+
+pub async fn pause_route(
+    Extension(write_lock): Extension<WriteLock>,
+    Extension(paths): Extension<Paths>,
+    headers: HeaderMap,
+    Form(body): Form<TimedOverrideBody>,
+) -> Response {
+    add_timed_override(write_lock, &paths, &headers, body, Action::Pause)
+}
+
+pub async fn cancel_route(
+    Extension(write_lock): Extension<WriteLock>,
+    Extension(paths): Extension<Paths>,
+    Form(body): Form<CancelBody>,
+) -> Response {
+    let cancelled = {
+        // Guard against concurrent writes
+        let _unused = write_lock.lock();
+        let mut overrides = match TimedOverrides::load_from(&paths.overrides) {
+            Ok(overrides) => overrides,
+            Err(error) => return internal_error(error),
+        };
+        let cancelled = overrides.cancel(&body.name);
+        if let Err(error) = overrides.save() {
+            return internal_error(error.into());
+        }
+        cancelled
+    };
+    if let (Some(cancelled), Ok(config)) = (cancelled, Config::load("config.json")) {
+        let _ = EmailClient::new(&config.email).send_timed_overrides_ended(&[cancelled]);
+    }
+    render_schedule(&paths, body.date)
+}
+
+fn add_timed_override(
+    write_lock: WriteLock,
+    paths: &Paths,
+    headers: &HeaderMap,
+    body: TimedOverrideBody,
+    action: Action,
+) -> Response {
+    let config = match Config::load("config.json") {
+        Ok(config) => config,
+        Err(error) => return internal_error(error),
+    };
+    if !config.schedules.iter().any(|s| s.name == body.name) {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("No schedule named {}", body.name),
+        )
+            .into_response();
+    }
+    let now = Local::now();
+    let end = match end_time(&now, body.hours, body.until) {
+        Ok(end) => end,
+        Err(error) => return (StatusCode::BAD_REQUEST, format!("{:#}", error)).into_response(),
+    };
+    let timed_override = TimedOverride {
+        name: body.name,
+        action,
+        start: now.fixed_offset(),
+        end: end.fixed_offset(),
+        author: author(headers).to_string(),
+    };
+
+    {
+        // Guard against concurrent writes
+        let _unused = write_lock.lock();
+        let mut overrides = match TimedOverrides::load_from(&paths.overrides) {
+            Ok(overrides) => overrides,
+            Err(error) => return internal_error(error),
+        };
+        overrides.add(timed_override.clone());
+        if let Err(error) = overrides.save() {
+            return internal_error(error.into());
+        }
+    }
+    let _ = EmailClient::new(&config.email).send_timed_override_started(&timed_override);
+    render_schedule(paths, body.date)
+}
+
+fn render_schedule(paths: &Paths, date: NaiveDate) -> Response {
+    let Some(schedule) = Schedule::load_from(&paths.schedules, date) else {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Schedule not found for {}", date),
+        )
+            .into_response();
+    };
+    ScheduleTemplate {
+        schedule: ScheduleModel::from_pin_schedules(
+            Local::now(),
+            date,
+            &schedule,
+            &TimedOverrides::load_from(&paths.overrides).unwrap_or_default(),
+        ),
+    }
+    .into_response()
+}
+
+fn internal_error(error: eyre::Report) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", error)).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct TimedOverrideBody {
+    /// Date of the schedule shown on the page
+    date: NaiveDate,
+
+    /// Name of the schedule to boost or pause
+    name: String,
+
+    #[serde(default, deserialize_with = "deserialize_optional")]
+    hours: Option<f64>,
+
+    #[serde(default, deserialize_with = "deserialize_optional")]
+    until: Option<NaiveTime>,
+}
+
+#[derive(Deserialize)]
+pub struct CancelBody {
+    date: NaiveDate,
+    name: String,
+}
+
+/// Treats empty form fields as missing
+fn deserialize_optional<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr,
+{
+    let value: Option<String> = Option::deserialize(deserializer)?;
+    match value.as_deref() {
+        None | Some("") => Ok(None),
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom("Invalid value")),
+    }
+}
@@ -1,72 +1,218 @@
 use askama::Template;
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Extension;
 use axum_extra::extract::Form;
-use chrono::{DateTime, Local, NaiveDate};
+use chrono::{DateTime, Days, Local, NaiveDate};
+use common::config::Config;
+use common::email::EmailClient;
+use common::overrides::{Action, TimedOverrides};
+use common::paths::Paths;
+use common::prices::PriceClient;
 use common::schedule::Schedule;
 use serde::de::Error;
 use serde::{Deserialize, Deserializer};
 use std::str::FromStr;
 
-use crate::date::{LocalExt, NaiveDateExt};
+use crate::date::{DateTimeExt, NaiveDateExt};
 use crate::lock::WriteLock;
+use crate::routes::author;
 
 pub async fn update_schedule_route(
     Extension(write_lock): Extension<WriteLock>,
+    Extension(paths): Extension<Paths>,
+    headers: HeaderMap,
     Form(body): Form<UpdateScheduleBody>,
 ) -> Response {
-    let Some(mut schedule) = Schedule::load_for_date(body.date) else {
+    let Some(mut schedule) = Schedule::load_from(&paths.schedules, body.date) else {
         return (
             StatusCode::NOT_FOUND,
             format!("Schedule not found for {}", body.date),
         )
             .into_response();
     };
-    let is_on = |pin: u8, hour_index: u32| {
-        body.pin_hours
+    let is_on = |pin_index: usize, slot_index: u32| {
+        body.pin_slots
             .iter()
-            .any(|(p, h)| *p == pin && *h == hour_index)
+            .any(|(p, s)| *p == pin_index && *s == slot_index)
     };
+    let author = author(&headers);
+    let timed_overrides = TimedOverrides::load_from(&paths.overrides).unwrap_or_default();
 
-    let current_hour = Local::current_hour();
-    for pin in &mut schedule.pins {
-        // Keep hours before the current hours as-is
-        let before_current_hour = pin.on_hours.iter().filter(|&t| t < &current_hour).copied();
+    let now = Local::now();
+    for (pin_index, pin) in schedule.pins.iter_mut().enumerate() {
+        let slot_length = pin.slot_length();
+        let current_slot = now.start_of_slot(slot_length);
 
-        // Update hours after the current hour
-        let after_current_hour =
-            body.date
-                .iter_hours()
-                .enumerate()
-                .filter_map(|(hour_index, hour)| {
-                    if hour >= current_hour && is_on(pin.pin, hour_index as u32) {
-                        Some(hour.fixed_offset())
-                    } else {
-                        None
-                    }
-                });
-        pin.on_hours = before_current_hour.chain(after_current_hour).collect();
+        // Slots before the current slot can't be changed anymore, and the slots of a boost or a
+        // pause follow it until it ends
+        for (slot_index, slot) in body.date.iter_slots(slot_length).enumerate() {
+            if slot >= current_slot
+                && timed_overrides
+                    .covering(&pin.name, &slot, slot_length)
+                    .is_none()
+            {
+                let on = is_on(pin_index, slot_index as u32);
+                pin.set_slot(slot.fixed_offset(), on, author, now.fixed_offset());
+            }
+        }
+    }
+
+    {
+        // Guard against concurrent writes
+        let _unused = write_lock.lock();
+        schedule.write_to(&paths.schedules, body.date).unwrap();
+    }
+
+    ScheduleTemplate {
+        schedule: ScheduleModel::from_pin_schedules(now, body.date, &schedule, &timed_overrides),
+    }
+    .into_response()
+}
+
+/// Gets the prices and computes the schedule for a date that doesn't have one yet
+pub async fn compute_schedule_route(
+    Extension(write_lock): Extension<WriteLock>,
+    Extension(paths): Extension<Paths>,
+    Form(body): Form<ComputeScheduleBody>,
+) -> Response {
+    let exists = || {
+        (
+            StatusCode::CONFLICT,
+            format!("Schedule already exists for {}", body.date),
+        )
+            .into_response()
+    };
+    if Schedule::load_from(&paths.schedules, body.date).is_some() {
+        return exists();
     }
+    let config = match Config::load("config.json") {
+        Ok(config) => config,
+        Err(error) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", error)).into_response()
+        }
+    };
 
+    let prices = match PriceClient::with_store(&config, paths.price_store())
+        .get_prices_for_date(body.date, config.price_area, config.vat_percent)
+        .await
     {
+        Ok(prices) => prices,
+        Err(error) => return (StatusCode::BAD_GATEWAY, format!("{:#}", error)).into_response(),
+    };
+    if let Some(mismatch) = &prices.mismatch {
+        let _ = EmailClient::new(&config.email).send_price_mismatch(body.date, mismatch);
+    }
+    let now = Local::now();
+
+    let schedule = {
+        // Guard against concurrent writes
+        let _unused = write_lock.lock();
+        if Schedule::load_from(&paths.schedules, body.date).is_some() {
+            return exists();
+        }
+        // Pins with a rolling window continue from the day before, and are optimized together
+        // with it unless it is over
+        let previous_date = body.date - Days::new(1);
+        let earlier = Schedule::load_from(&paths.schedules, previous_date - Days::new(1));
+        let mut previous = Schedule::load_from(&paths.schedules, previous_date);
+        let mut schedule = Schedule::compute_for_date(
+            &config,
+            &prices.prices,
+            earlier.as_ref(),
+            previous.as_mut(),
+            &now,
+        );
+        schedule.source = Some(prices.source.to_string());
+        if let Some(previous) = previous.filter(|_| previous_date >= now.date_naive()) {
+            previous.write_to(&paths.schedules, previous_date).unwrap();
+        }
+        schedule.write_to(&paths.schedules, body.date).unwrap();
+        schedule
+    };
+
+    let timed_overrides = TimedOverrides::load_from(&paths.overrides).unwrap_or_default();
+    ScheduleTemplate {
+        schedule: ScheduleModel::from_pin_schedules(now, body.date, &schedule, &timed_overrides),
+    }
+    .into_response()
+}
+
+/// Recomputes the schedule with the current config and the saved prices, keeping past slots. The
+/// next day's schedule is recomputed too if it exists and some pins are optimized over a rolling
+/// window.
+pub async fn recompute_schedule_route(
+    Extension(write_lock): Extension<WriteLock>,
+    Extension(paths): Extension<Paths>,
+    Form(body): Form<ComputeScheduleBody>,
+) -> Response {
+    let config = match Config::load("config.json") {
+        Ok(config) => config,
+        Err(error) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", error)).into_response()
+        }
+    };
+
+    let now = Local::now();
+    let (dates, old, new) = {
         // Guard against concurrent writes
         let _unused = write_lock.lock();
-        schedule.write_to_file(body.date).unwrap();
+        let Some(old) = Schedule::load_from(&paths.schedules, body.date) else {
+            return (
+                StatusCode::NOT_FOUND,
+                format!("Schedule not found for {}", body.date),
+            )
+                .into_response();
+        };
+        let mut dates = vec![body.date];
+        let mut old = vec![old];
+        let next_date = body.date + Days::new(1);
+        if config.schedules.iter().any(|config| config.rolling_window) {
+            if let Some(next) = Schedule::load_from(&paths.schedules, next_date) {
+                dates.push(next_date);
+                old.push(next);
+            }
+        }
+        let store = paths.price_store();
+        let reloaded = dates
+            .iter()
+            .zip(&old)
+            .map(|(date, schedule)| {
+                let mut schedule = schedule.clone();
+                schedule.reload_prices(&config, &store, *date);
+                schedule
+            })
+            .collect::<Vec<_>>();
+        let earlier = Schedule::load_from(&paths.schedules, body.date - Days::new(1));
+        let new = Schedule::recompute(&config, earlier.as_ref(), &reloaded, &now);
+        for (date, schedule) in dates.iter().zip(&new) {
+            schedule.write_to(&paths.schedules, *date).unwrap();
+        }
+        (dates, old, new)
+    };
+    let timed_overrides = TimedOverrides::load_from(&paths.overrides).unwrap_or_default();
+    let email_client = EmailClient::new(&config.email);
+    for ((date, old), new) in dates.iter().zip(&old).zip(&new) {
+        let _ = email_client.send_schedule_changes(*date, old, new, &timed_overrides);
     }
 
     ScheduleTemplate {
-        schedule: ScheduleModel::from_pin_schedules(current_hour, body.date, &schedule),
+        schedule: ScheduleModel::from_pin_schedules(now, body.date, &new[0], &timed_overrides),
     }
     .into_response()
 }
 
+#[derive(Deserialize)]
+pub struct ComputeScheduleBody {
+    date: NaiveDate,
+}
+
 #[derive(Deserialize)]
 pub struct UpdateScheduleBody {
     date: NaiveDate,
 
     #[serde(default, deserialize_with = "deserialize_pairs")]
-    pin_hours: Vec<(u8, u32)>,
+    pin_slots: Vec<(usize, u32)>,
 }
 
 fn deserialize_pairs<'de, D, T1, T2>(deserializer: D) -> Result<Vec<(T1, T2)>, D::Error>
@@ -75,23 +221,23 @@ where
     T1: FromStr,
     T2: FromStr,
 {
-    let hours: Vec<String> = Vec::deserialize(deserializer)?;
+    let pairs: Vec<String> = Vec::deserialize(deserializer)?;
     let err = || Error::custom("Invalid format");
-    hours
+    pairs
         .iter()
-        .map(|hour| {
-            let (pin, hour_index) = hour.split_once(',').ok_or_else(err)?;
-            let pin = pin.parse::<T1>().map_err(|_| err())?;
-            let hour_index = hour_index.parse::<T2>().map_err(|_| err())?;
-            Ok((pin, hour_index))
+        .map(|pair| {
+            let (first, second) = pair.split_once(',').ok_or_else(err)?;
+            let first = first.parse::<T1>().map_err(|_| err())?;
+            let second = second.parse::<T2>().map_err(|_| err())?;
+            Ok((first, second))
         })
         .collect::<Result<Vec<_>, _>>()
 }
 
 #[derive(Template)]
 #[template(path = "components/schedule.html")]
-struct ScheduleTemplate {
-    schedule: ScheduleModel,
+pub struct ScheduleTemplate {
+    pub schedule: ScheduleModel,
 }
 
 pub struct ScheduleModel {
@@ -99,55 +245,226 @@ pub struct ScheduleModel {
     pub past: bool,
     pub pins: Vec<PinInfo>,
     pub avg_price: f64,
+    pub energy_kwh: Option<f64>,
+    pub cost_eur: Option<f64>,
 }
 
 pub struct PinInfo {
     pub name: String,
-    pub pin: u8,
-    pub hours: Vec<HourInfo>,
-    pub num_on_hours: usize,
+
+    /// Position of the pin in the schedule. Network relays don't have a pin number.
+    pub index: usize,
+    pub slots: Vec<SlotInfo>,
+    pub on_hours: f64,
     pub avg_price: f64,
+    pub energy_kwh: Option<f64>,
+    pub cost_eur: Option<f64>,
+
+    /// Description of the active boost or pause, if any
+    pub timed_override: Option<String>,
 }
 
-pub struct HourInfo {
-    pub hour: String,
+pub struct SlotInfo {
+    pub time: String,
     pub on: bool,
     pub past: bool,
     pub price: f64,
+
+    /// Description of the manual override of the slot, if any
+    pub override_info: Option<String>,
+
+    /// Description of the boost or pause covering the slot, if any. Such slots can't be changed.
+    pub timed_override_info: Option<String>,
 }
 
 impl ScheduleModel {
     pub fn from_pin_schedules(
-        current_hour: DateTime<Local>,
+        now: DateTime<Local>,
         date: NaiveDate,
         schedule: &Schedule,
+        timed_overrides: &TimedOverrides,
     ) -> Self {
+        // The totals include the boosts and pauses, but the slots show them apart from the
+        // manual changes, since they are not saved in the schedule
+        let resolved = schedule.with_timed_overrides(timed_overrides);
+        let prices = schedule.effective_prices();
         Self {
             date,
-            past: date < current_hour.naive_local().date(),
+            past: date < now.naive_local().date(),
             pins: schedule
                 .pins
                 .iter()
-                .map(|pin| PinInfo {
-                    name: pin.name.clone(),
-                    pin: pin.pin,
-                    hours: date
-                        .iter_hours()
-                        .map(|hour| {
-                            let on = pin.on_hours.iter().any(|&t| t == hour);
-                            HourInfo {
-                                hour: hour.format("%H").to_string(),
-                                on,
-                                past: hour < current_hour,
-                                price: schedule.avg_price_for_hour(hour).unwrap(),
-                            }
-                        })
-                        .collect(),
-                    num_on_hours: pin.on_hours.len(),
-                    avg_price: pin.avg_price(&schedule.prices, true),
+                .zip(&resolved.pins)
+                .enumerate()
+                .map(|(index, (pin, resolved_pin))| {
+                    let slot_length = pin.slot_length();
+                    let current_slot = now.start_of_slot(slot_length);
+                    PinInfo {
+                        name: pin.name.clone(),
+                        index,
+                        slots: date
+                            .iter_slots(slot_length)
+                            .map(|slot| {
+                                let timed_override =
+                                    timed_overrides.covering(&pin.name, &slot, slot_length);
+                                SlotInfo {
+                                    time: slot.format("%H:%M").to_string(),
+                                    on: resolved_pin.is_on(&slot),
+                                    past: slot < current_slot,
+                                    price: schedule.avg_price_for_slot(slot, slot_length).unwrap(),
+                                    override_info: pin.override_for(&slot.fixed_offset()).map(
+                                        |slot_override| {
+                                            format!(
+                                                "Muutettu käsin: {} {}",
+                                                slot_override.author,
+                                                slot_override.timestamp.format("%d.%m.%Y %H:%M")
+                                            )
+                                        },
+                                    ),
+                                    timed_override_info: timed_override.map(|o| {
+                                        format!("{}: {}", action_name(o.action), o.author)
+                                    }),
+                                }
+                            })
+                            .collect(),
+                        on_hours: resolved_pin.on_duration().num_minutes() as f64 / 60.0,
+                        avg_price: resolved_pin.avg_price(&prices, true),
+                        energy_kwh: resolved_pin.energy_kwh(),
+                        cost_eur: resolved_pin.cost_eur(&prices),
+                        timed_override: timed_overrides.active(&pin.name, &now).map(|o| {
+                            format!(
+                                "{} {} asti",
+                                action_name(o.action),
+                                o.end.format("%d.%m. %H:%M")
+                            )
+                        }),
+                    }
                 })
                 .collect(),
-            avg_price: schedule.avg_price(),
+            avg_price: resolved.avg_price(),
+            energy_kwh: resolved.energy_kwh(),
+            cost_eur: resolved.cost_eur(),
+        }
+    }
+}
+
+fn action_name(action: Action) -> &'static str {
+    match action {
+        Action::Boost => "Tehostettu",
+        Action::Pause => "Tauolla",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{update_schedule_route, UpdateScheduleBody};
+    use crate::date::NaiveDateExt;
+    use crate::lock::WriteLock;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::Extension;
+    use axum_extra::extract::Form;
+    use chrono::{Duration, Local};
+    use common::config::OutputConfig;
+    use common::overrides::{Action, TimedOverride, TimedOverrides};
+    use common::paths::Paths;
+    use common::prices::Price;
+    use common::schedule::{PinSchedule, Schedule};
+    use std::path::PathBuf;
+
+    /// Temporary directory that is removed when dropped, even if the test fails
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("sahko-web-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn paths(&self) -> Paths {
+            Paths {
+                schedules: self.0.join("schedules"),
+                overrides: self.0.join("overrides.json"),
+                prices: self.0.join("prices"),
+            }
         }
     }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_during_boost() {
+        let dir = TempDir::new("save-during-boost");
+        let paths = dir.paths();
+        let now = Local::now();
+        let today = now.date_naive();
+        let hour = Duration::hours(1);
+        let schedule = Schedule {
+            pins: vec![PinSchedule {
+                name: "Boiler".to_string(),
+                outputs: vec![OutputConfig::default()],
+                slot_minutes: 60,
+                on_slots: Vec::new(),
+                power_kw: None,
+                overrides: Vec::new(),
+            }],
+            prices: today
+                .iter_slots(hour)
+                .map(|slot| Price {
+                    validity: slot.fixed_offset(),
+                    price: 1.0,
+                })
+                .collect(),
+            tariff: None,
+            source: None,
+        };
+        schedule.write_to(&paths.schedules, today).unwrap();
+
+        let mut timed_overrides = TimedOverrides::load_from(&paths.overrides).unwrap();
+        timed_overrides.add(TimedOverride {
+            name: "Boiler".to_string(),
+            action: Action::Boost,
+            start: now.fixed_offset(),
+            end: (now + Duration::hours(2)).fixed_offset(),
+            author: "test".to_string(),
+        });
+        timed_overrides.save().unwrap();
+
+        // The grid shows the boosted slots as on, and they are posted back as such
+        let boosted = today
+            .iter_slots(hour)
+            .enumerate()
+            .filter(|(_, slot)| timed_overrides.covering("Boiler", slot, hour).is_some())
+            .map(|(index, _)| (0, index as u32))
+            .collect::<Vec<_>>();
+        assert!(!boosted.is_empty());
+        let save = || {
+            update_schedule_route(
+                Extension(WriteLock::new()),
+                Extension(paths.clone()),
+                HeaderMap::new(),
+                Form(UpdateScheduleBody {
+                    date: today,
+                    pin_slots: boosted.clone(),
+                }),
+            )
+        };
+
+        let response = save().await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let saved = Schedule::load_from(&paths.schedules, today).unwrap();
+        assert_eq!(saved.pins[0].overrides, Vec::new());
+
+        // Without the boost, the same slots are changed manually
+        std::fs::remove_file(&paths.overrides).unwrap();
+        save().await;
+        let saved = Schedule::load_from(&paths.schedules, today).unwrap();
+        assert_eq!(saved.pins[0].overrides.len(), boosted.len());
+    }
 }
@@ -1,6 +1,8 @@
 use askama::Template;
 use axum::response::IntoResponse;
+use axum::Extension;
 use chrono::{Datelike, Duration, Local, NaiveDate};
+use common::paths::Paths;
 use common::prices::round_price;
 use common::schedule::Schedule;
 
@@ -16,12 +18,12 @@ struct MonthStats {
 }
 
 impl MonthStats {
-    fn for_month(year: i32, month: u32) -> Option<Self> {
+    fn for_month(paths: &Paths, year: i32, month: u32) -> Option<Self> {
         let mut date = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
         let mut total: f64 = 0.0;
         let mut count: u32 = 0;
         loop {
-            if let Some(schedule) = Schedule::load_for_date(date) {
+            if let Some(schedule) = Schedule::load_from(&paths.schedules, date) {
                 total += schedule.avg_price();
                 count += 1;
             }
@@ -43,13 +45,13 @@ impl MonthStats {
 
 const START_YEAR: i32 = 2023;
 
-pub async fn stats_route() -> impl IntoResponse {
+pub async fn stats_route(Extension(paths): Extension<Paths>) -> impl IntoResponse {
     let today = Local::now().date_naive();
     let mut year = START_YEAR;
     let mut month = 1;
     let mut stats: Vec<MonthStats> = Vec::new();
     while year <= today.year() || (year == today.year() && month <= today.month()) {
-        if let Some(month_stats) = MonthStats::for_month(year, month) {
+        if let Some(month_stats) = MonthStats::for_month(&paths, year, month) {
             stats.push(month_stats);
         }
         month += 1;
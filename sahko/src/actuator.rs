@@ -0,0 +1,216 @@
+use eyre::{eyre, Report, Result};
+use std::cmp::Reverse;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use common::config::{ActuatorConfig, OutputConfig};
+
+use crate::gpio::gpio_actuator;
+use crate::relay::{ShellyActuator, TasmotaActuator};
+
+/// What happened when an actuator was set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Unchanged,
+    Changed,
+
+    /// The previous state was unknown, e.g. after a power outage
+    Initialized,
+}
+
+/// Device that turns a switch on and off
+pub trait Actuator {
+    fn set(&self, on: bool) -> Result<Transition>;
+}
+
+/// Returns the actuator for a switch. GPIO pins are simulated with the file `simulated_gpio` if
+/// given.
+pub fn actuator_for(
+    config: &OutputConfig,
+    simulated_gpio: Option<&Path>,
+) -> Result<Box<dyn Actuator>> {
+    Ok(match &config.actuator {
+        ActuatorConfig::Gpio(line_config) => {
+            let pin = config
+                .pin
+                .ok_or_else(|| eyre!("No pin for the GPIO output"))?;
+            gpio_actuator(pin, line_config, simulated_gpio)
+        }
+        ActuatorConfig::Shelly { url, switch_id } => Box::new(ShellyActuator::new(url, *switch_id)),
+        ActuatorConfig::Tasmota { url, relay } => Box::new(TasmotaActuator::new(url, *relay)),
+    })
+}
+
+/// Switch of a schedule and the state it should be in
+pub struct Output {
+    /// Index of the schedule
+    pub schedule: usize,
+
+    /// Description of the switch, if the schedule has several
+    pub description: Option<String>,
+    pub actuator: Box<dyn Actuator>,
+    pub on: bool,
+
+    /// Outputs with a higher priority are turned on first
+    pub priority: i32,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateChange {
+    None,
+    Change {
+        /// Indices of the outputs whose state was changed
+        changed_outputs: Vec<usize>,
+        powered_on: bool,
+    },
+}
+
+/// Sets each output to its state and returns the outputs whose state was changed, and the errors
+/// of the outputs that could not be set. Outputs are turned off first, and then on one at a time in
+/// priority order, pausing for `stagger` after each output that was turned on.
+pub fn set_states(outputs: &[Output], stagger: Duration) -> (StateChange, Vec<(usize, Report)>) {
+    let mut powered_on = false;
+    let mut changed_outputs = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut order = (0..outputs.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| (outputs[i].on, Reverse(outputs[i].priority)));
+    let mut turned_on = false;
+    for i in order {
+        let output = &outputs[i];
+        if output.on && turned_on && !stagger.is_zero() {
+            thread::sleep(stagger);
+        }
+        let transition = match output.actuator.set(output.on) {
+            Ok(transition) => transition,
+            Err(error) => {
+                errors.push((i, error));
+                turned_on = false;
+                continue;
+            }
+        };
+        match transition {
+            Transition::Unchanged => (),
+            Transition::Changed => changed_outputs.push(i),
+            Transition::Initialized => {
+                powered_on = true;
+                changed_outputs.push(i);
+            }
+        }
+        turned_on = output.on && transition != Transition::Unchanged;
+    }
+    changed_outputs.sort();
+
+    let state_change = if changed_outputs.is_empty() {
+        StateChange::None
+    } else {
+        StateChange::Change {
+            changed_outputs,
+            powered_on,
+        }
+    };
+    (state_change, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_states, Actuator, Output, StateChange, Transition};
+    use eyre::{eyre, Result};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    /// Records the order in which the outputs are set
+    struct RecordingActuator {
+        name: &'static str,
+        on: bool,
+        log: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl Actuator for RecordingActuator {
+        fn set(&self, on: bool) -> Result<Transition> {
+            self.log.borrow_mut().push(self.name);
+            if self.name == "broken" {
+                return Err(eyre!("Relay not responding"));
+            }
+            Ok(if on == self.on {
+                Transition::Unchanged
+            } else {
+                Transition::Changed
+            })
+        }
+    }
+
+    #[test]
+    fn test_switching_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let output = |name, was_on, on, priority| Output {
+            schedule: 0,
+            description: None,
+            actuator: Box::new(RecordingActuator {
+                name,
+                on: was_on,
+                log: log.clone(),
+            }),
+            on,
+            priority,
+        };
+        let outputs = [
+            output("heater", false, true, 0),
+            output("boiler", false, true, 1),
+            output("sauna", true, false, 2),
+            output("lights", true, true, 0),
+        ];
+        let (state_change, errors) = set_states(&outputs, Duration::from_millis(10));
+        assert_eq!(
+            state_change,
+            StateChange::Change {
+                changed_outputs: vec![0, 1, 2],
+                powered_on: false,
+            }
+        );
+        assert!(errors.is_empty());
+        assert_eq!(*log.borrow(), vec!["sauna", "boiler", "heater", "lights"]);
+    }
+
+    #[test]
+    fn test_errors_do_not_stop_switching() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let output = |name, on| Output {
+            schedule: 0,
+            description: None,
+            actuator: Box::new(RecordingActuator {
+                name,
+                on: false,
+                log: log.clone(),
+            }),
+            on,
+            priority: 0,
+        };
+        let outputs = [
+            output("broken", true),
+            output("boiler", true),
+            output("broken", false),
+        ];
+        let (state_change, errors) = set_states(&outputs, Duration::ZERO);
+        assert_eq!(
+            state_change,
+            StateChange::Change {
+                changed_outputs: vec![1],
+                powered_on: false,
+            }
+        );
+        assert_eq!(
+            errors
+                .iter()
+                .map(|(i, error)| (*i, error.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                (2, "Relay not responding".to_string()),
+                (0, "Relay not responding".to_string())
+            ]
+        );
+        assert_eq!(*log.borrow(), vec!["broken", "broken", "boiler"]);
+    }
+}
@@ -1,43 +1,234 @@
 use eyre::Result;
-use gpio_cdev::{Chip, LineDirection, LineRequestFlags};
-
-pub enum StateChange {
-    None,
-    Change {
-        changed_pins: Vec<usize>,
-        powered_on: bool,
-    },
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use common::config::GpioLineConfig;
+
+use crate::actuator::{Actuator, Transition};
+
+/// Returns an actuator for `pin`, simulated with the file `simulated` if given. The chip is always
+/// simulated on other OSes than Linux.
+pub fn gpio_actuator(
+    pin: u8,
+    line_config: &GpioLineConfig,
+    simulated: Option<&Path>,
+) -> Box<dyn Actuator> {
+    match simulated {
+        Some(path) => Box::new(SimulatedGpioActuator::new(path, pin, line_config)),
+        #[cfg(target_os = "linux")]
+        None => Box::new(GpioActuator::new(pin, line_config)),
+        #[cfg(not(target_os = "linux"))]
+        None => Box::new(SimulatedGpioActuator::new(
+            Path::new(SIMULATED_GPIO_FILE_NAME),
+            pin,
+            line_config,
+        )),
+    }
 }
 
-/// Returns indices of pins whose state was changed
-pub fn set_pin_states(pins: &[(u8, bool)]) -> Result<StateChange> {
-    let mut powered_on = false;
-    let mut changed_pins = Vec::new();
-
-    let mut chip = Chip::new("/dev/gpiochip0")?;
-    for (i, (pin, state)) in pins.iter().enumerate() {
-        let value = u8::from(*state);
-
-        let line = chip.get_line(*pin as u32)?;
-        if line.info()?.direction() != LineDirection::Out {
-            powered_on = true;
-            changed_pins.push(i);
-        } else {
-            let line_handle = line.request(LineRequestFlags::empty(), 0, "sahko")?;
-            let current_value = line_handle.get_value()?;
-            if current_value != value {
-                changed_pins.push(i);
+#[cfg(not(target_os = "linux"))]
+const SIMULATED_GPIO_FILE_NAME: &str = "gpio.json";
+
+#[cfg(target_os = "linux")]
+pub use linux::GpioActuator;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use eyre::Result;
+    use gpio_cdev::{Chip, LineDirection, LineRequestFlags};
+
+    use common::config::{Bias, Drive, GpioLineConfig};
+
+    use crate::actuator::{Actuator, Transition};
+
+    // Bias flags of the v1 character device ABI (Linux 5.5), not exposed by gpio-cdev
+    const BIAS_PULL_UP: u32 = 1 << 5;
+    const BIAS_PULL_DOWN: u32 = 1 << 6;
+    const BIAS_DISABLE: u32 = 1 << 7;
+
+    pub struct GpioActuator {
+        chip: String,
+        pin: u8,
+        flags: u32,
+    }
+
+    impl GpioActuator {
+        pub fn new(pin: u8, config: &GpioLineConfig) -> Self {
+            Self {
+                chip: config.chip().to_string(),
+                pin,
+                flags: request_flags(config).bits(),
             }
         }
-        line.request(LineRequestFlags::OUTPUT, value, "sahko")?;
     }
 
-    Ok(if changed_pins.is_empty() {
-        StateChange::None
-    } else {
-        StateChange::Change {
-            changed_pins,
-            powered_on,
+    /// Flags for requesting the line as an output
+    pub(super) fn request_flags(config: &GpioLineConfig) -> LineRequestFlags {
+        let mut flags = LineRequestFlags::OUTPUT;
+        if config.active_low {
+            flags |= LineRequestFlags::ACTIVE_LOW;
+        }
+        flags |= match config.drive {
+            None | Some(Drive::PushPull) => LineRequestFlags::empty(),
+            Some(Drive::OpenDrain) => LineRequestFlags::OPEN_DRAIN,
+            Some(Drive::OpenSource) => LineRequestFlags::OPEN_SOURCE,
+        };
+        flags |= LineRequestFlags::from_bits_retain(match config.bias {
+            None => 0,
+            Some(Bias::Disabled) => BIAS_DISABLE,
+            Some(Bias::PullUp) => BIAS_PULL_UP,
+            Some(Bias::PullDown) => BIAS_PULL_DOWN,
+        });
+        flags
+    }
+
+    impl Actuator for GpioActuator {
+        fn set(&self, on: bool) -> Result<Transition> {
+            let value = u8::from(on);
+
+            let mut chip = Chip::new(&self.chip)?;
+            let line = chip.get_line(self.pin as u32)?;
+            let transition = if line.info()?.direction() != LineDirection::Out {
+                Transition::Initialized
+            } else {
+                // Read the value as active low too, so that it can be compared with `on`
+                let flags = self.flags & LineRequestFlags::ACTIVE_LOW.bits();
+                let line_handle =
+                    line.request(LineRequestFlags::from_bits_retain(flags), 0, "sahko")?;
+                if line_handle.get_value()? != value {
+                    Transition::Changed
+                } else {
+                    Transition::Unchanged
+                }
+            };
+            line.request(
+                LineRequestFlags::from_bits_retain(self.flags),
+                value,
+                "sahko",
+            )?;
+            Ok(transition)
+        }
+    }
+}
+
+/// GPIO chips simulated with a JSON file of the output lines and their levels (true for high) per
+/// chip, so that the states persist across runs like on a real chip. Lines missing from the file
+/// are not yet outputs, as after a power outage.
+pub struct SimulatedGpioActuator {
+    path: PathBuf,
+    chip: String,
+    pin: u8,
+    active_low: bool,
+}
+
+/// Levels of the output lines by chip and pin
+pub type SimulatedLines = BTreeMap<String, BTreeMap<u8, bool>>;
+
+impl SimulatedGpioActuator {
+    pub fn new(path: &Path, pin: u8, config: &GpioLineConfig) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            chip: config.chip().to_string(),
+            pin,
+            active_low: config.active_low,
+        }
+    }
+
+    /// Reads the levels of the output lines
+    pub fn read_lines(path: &Path) -> Result<SimulatedLines> {
+        match File::open(path) {
+            Ok(file) => Ok(serde_json::from_reader(file)?),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(error) => Err(error.into()),
         }
-    })
+    }
+}
+
+impl Actuator for SimulatedGpioActuator {
+    fn set(&self, on: bool) -> Result<Transition> {
+        let mut lines = Self::read_lines(&self.path)?;
+        let level = on != self.active_low;
+        let previous = lines
+            .entry(self.chip.clone())
+            .or_default()
+            .insert(self.pin, level);
+        let transition = match previous {
+            None => Transition::Initialized,
+            Some(previous) if previous != level => Transition::Changed,
+            Some(_) => Transition::Unchanged,
+        };
+        write!(
+            File::create(&self.path)?,
+            "{}",
+            serde_json::to_string_pretty(&lines)?
+        )?;
+        Ok(transition)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Actuator, SimulatedGpioActuator, Transition};
+    use common::config::GpioLineConfig;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_request_flags() {
+        use super::linux::request_flags;
+        use common::config::{Bias, Drive};
+        use gpio_cdev::LineRequestFlags;
+
+        assert_eq!(
+            request_flags(&GpioLineConfig::default()).bits(),
+            LineRequestFlags::OUTPUT.bits()
+        );
+        let flags = request_flags(&GpioLineConfig {
+            chip: Some("/dev/gpiochip4".to_string()),
+            active_low: true,
+            bias: Some(Bias::PullUp),
+            drive: Some(Drive::OpenDrain),
+        });
+        assert_eq!(
+            flags.bits(),
+            (LineRequestFlags::OUTPUT
+                | LineRequestFlags::ACTIVE_LOW
+                | LineRequestFlags::OPEN_DRAIN)
+                .bits()
+                | 1 << 5
+        );
+    }
+
+    #[test]
+    fn test_simulated_gpio() {
+        let path = std::env::temp_dir().join(format!("sahko-gpio-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let actuator = SimulatedGpioActuator::new(&path, 17, &GpioLineConfig::default());
+        assert_eq!(actuator.set(true).unwrap(), Transition::Initialized);
+        assert_eq!(actuator.set(true).unwrap(), Transition::Unchanged);
+        assert_eq!(actuator.set(false).unwrap(), Transition::Changed);
+
+        // Same pin on another chip, on when the line is low
+        let active_low = SimulatedGpioActuator::new(
+            &path,
+            17,
+            &GpioLineConfig {
+                chip: Some("/dev/gpiochip4".to_string()),
+                active_low: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(active_low.set(true).unwrap(), Transition::Initialized);
+        assert_eq!(active_low.set(true).unwrap(), Transition::Unchanged);
+        assert_eq!(
+            SimulatedGpioActuator::read_lines(&path).unwrap(),
+            [
+                ("/dev/gpiochip0".to_string(), [(17, false)].into()),
+                ("/dev/gpiochip4".to_string(), [(17, false)].into()),
+            ]
+            .into()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -1,39 +1,83 @@
+mod actuator;
 mod gpio;
+mod relay;
 
-use chrono::NaiveTime;
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime};
 use eyre::{eyre, Result};
 use pico_args::Arguments;
+use std::cmp::Reverse;
 use std::path::PathBuf;
 use std::time::Duration;
 
 use common::config::{Config, ScheduleConfig};
 use common::domain::RelativeDate;
-use common::email::EmailClient;
-use common::prices::PriceClient;
+use common::email::{EmailClient, OutputStateChange};
+use common::overrides::{end_time, Action, TimedOverride, TimedOverrides};
+use common::paths::Paths;
+use common::prices::{BlockingPriceClient, DayPrices};
 use common::schedule::Schedule;
 
-use crate::gpio::{set_pin_states, StateChange};
+use crate::actuator::{actuator_for, set_states, Output, StateChange};
 
 const MAKE_TOMORROWS_SCHEDULE: (u32, u32) = (15, 5);
 
+fn price_client(config: &Config, paths: &Paths) -> Result<BlockingPriceClient> {
+    BlockingPriceClient::with_store(config, paths.price_store())
+}
+
 fn main() -> Result<()> {
     let mut args = Arguments::from_env();
     env_logger::Builder::new()
         .filter_level(log::LevelFilter::Debug)
         .init();
 
-    let config = Config::load("config.json")?;
+    let mut config = Config::load("config.json")?;
+    if let Some(path) = args.opt_value_from_str::<_, PathBuf>("--simulated-gpio")? {
+        config.simulated_gpio = Some(path);
+    }
     let email_client = EmailClient::new(&config.email);
+    let paths = Paths::default();
 
     if args.contains(["-h", "--help"]) {
         let bin = PathBuf::from(std::env::args_os().next().unwrap_or_default());
         let bin = bin.file_name().unwrap_or_default().to_string_lossy();
-        println!("Usage: {bin} [--send-schedules]");
+        println!("Usage: {bin} [--simulated-gpio FILE] [--send-schedules | --recompute [DATE]]");
+        println!("       {bin} (--boost | --pause) NAME (--hours HOURS | --until HH:MM)");
+        println!("       {bin} --cancel NAME");
         return Ok(());
     }
     if args.contains("--send-schedules") {
-        send_schedules(&config, &email_client)
-    } else if let Err(error) = run(&config.schedules, &email_client) {
+        send_schedules(&config, &email_client, &paths)
+    } else if args.contains("--recompute") {
+        let date = args.opt_free_from_str::<NaiveDate>()?;
+        recompute(&config, &email_client, &paths, date)
+    } else if let Some(name) = args.opt_value_from_str::<_, String>("--boost")? {
+        add_timed_override(
+            &config,
+            &email_client,
+            &paths,
+            &mut args,
+            name,
+            Action::Boost,
+        )
+    } else if let Some(name) = args.opt_value_from_str::<_, String>("--pause")? {
+        add_timed_override(
+            &config,
+            &email_client,
+            &paths,
+            &mut args,
+            name,
+            Action::Pause,
+        )
+    } else if let Some(name) = args.opt_value_from_str::<_, String>("--cancel")? {
+        let mut overrides = TimedOverrides::load_from(&paths.overrides)?;
+        let cancelled = overrides
+            .cancel(&name)
+            .ok_or_else(|| eyre!("No boost or pause for {}", name))?;
+        overrides.save()?;
+        let _ = email_client.send_timed_overrides_ended(&[cancelled]);
+        Ok(())
+    } else if let Err(error) = run(&config, &email_client, &paths, Local::now()) {
         let _ = email_client.send_error(&error);
         Err(error)
     } else {
@@ -41,33 +85,177 @@ fn main() -> Result<()> {
     }
 }
 
-fn send_schedules(config: &Config, email_client: &EmailClient) -> Result<()> {
-    let price_client = PriceClient::new();
-    for date in [RelativeDate::Today, RelativeDate::Tomorrow] {
-        let (schedule, _) = ensure_schedule(date, &price_client, &config.schedules)?;
-        let _ = email_client.send_schedule(date.to_naive_date(), &schedule);
+fn send_schedules(config: &Config, email_client: &EmailClient, paths: &Paths) -> Result<()> {
+    let price_client = price_client(config, paths)?;
+    let timed_overrides = TimedOverrides::load_from(&paths.overrides)?;
+    let now = Local::now();
+    let today = now.date_naive();
+    let (mut schedule, _) = ensure_schedule(
+        today,
+        &price_client,
+        config,
+        email_client,
+        paths,
+        None,
+        &now,
+    )?;
+    let (tomorrows_schedule, _) = ensure_schedule(
+        today + Days::new(1),
+        &price_client,
+        config,
+        email_client,
+        paths,
+        Some(&mut schedule),
+        &now,
+    )?;
+    for (date, schedule) in [
+        (today, schedule),
+        (today + Days::new(1), tomorrows_schedule),
+    ] {
+        let _ = email_client.send_schedule(date, &schedule, &timed_overrides);
         std::thread::sleep(Duration::from_secs(1));
     }
     Ok(())
 }
 
-fn run(config: &[ScheduleConfig], email_client: &EmailClient) -> Result<()> {
-    let price_client = PriceClient::new();
-    let now = chrono::Local::now();
+/// Recomputes the schedule for `date`, or today's and tomorrow's schedules if not given, with the
+/// current config and the saved prices
+fn recompute(
+    config: &Config,
+    email_client: &EmailClient,
+    paths: &Paths,
+    date: Option<NaiveDate>,
+) -> Result<()> {
+    let dates = match date {
+        Some(date) => vec![date],
+        None => vec![
+            RelativeDate::Today.to_naive_date(),
+            RelativeDate::Tomorrow.to_naive_date(),
+        ],
+    };
+    let explicit = date.is_some();
+    let mut old = Vec::new();
+    for date in dates {
+        match Schedule::load_from(&paths.schedules, date) {
+            Some(schedule) => old.push((date, schedule)),
+            None if explicit => return Err(eyre!("No schedule for {}", date)),
+            // Tomorrow's schedule may not exist yet
+            None => {}
+        }
+    }
+    let Some(&(first_date, _)) = old.first() else {
+        return Ok(());
+    };
 
-    let (schedule, created) = ensure_schedule(RelativeDate::Today, &price_client, config)?;
+    let timed_overrides = TimedOverrides::load_from(&paths.overrides)?;
+    let earlier = Schedule::load_from(&paths.schedules, first_date - Days::new(1));
+    let store = paths.price_store();
+    let old_schedules = old
+        .iter()
+        .map(|(date, schedule)| {
+            let mut schedule = schedule.clone();
+            schedule.reload_prices(config, &store, *date);
+            schedule
+        })
+        .collect::<Vec<_>>();
+    let new = Schedule::recompute(config, earlier.as_ref(), &old_schedules, &Local::now());
+    for ((date, old), new) in old.iter().zip(&new) {
+        log::info!("Saving the recomputed schedule for {}", date);
+        new.write_to(&paths.schedules, *date)?;
+        let _ = email_client.send_schedule_changes(*date, old, new, &timed_overrides);
+    }
+    Ok(())
+}
+
+/// Boosts or pauses the schedule `name` for `--hours` or `--until` a time of day
+fn add_timed_override(
+    config: &Config,
+    email_client: &EmailClient,
+    paths: &Paths,
+    args: &mut Arguments,
+    name: String,
+    action: Action,
+) -> Result<()> {
+    if !config
+        .schedules
+        .iter()
+        .any(|schedule| schedule.name == name)
+    {
+        return Err(eyre!("No schedule named {}", name));
+    }
+    let now = Local::now();
+    let hours = args.opt_value_from_str::<_, f64>("--hours")?;
+    let until = args.opt_value_from_str::<_, NaiveTime>("--until")?;
+    let timed_override = TimedOverride {
+        name,
+        action,
+        start: now.fixed_offset(),
+        end: end_time(&now, hours, until)?.fixed_offset(),
+        author: "cli".to_string(),
+    };
+
+    let mut overrides = TimedOverrides::load_from(&paths.overrides)?;
+    overrides.add(timed_override.clone());
+    overrides.save()?;
+    let _ = email_client.send_timed_override_started(&timed_override);
+    Ok(())
+}
+
+/// Makes the schedules that are missing and sets the outputs to the state they should be in at
+/// `now`
+fn run(
+    config: &Config,
+    email_client: &EmailClient,
+    paths: &Paths,
+    now: DateTime<Local>,
+) -> Result<StateChange> {
+    // Boosts and pauses take precedence over the schedule until they expire
+    let mut timed_overrides = match TimedOverrides::load_from(&paths.overrides) {
+        Ok(timed_overrides) => timed_overrides,
+        Err(error) => {
+            // Keep following the schedules rather than stop switching altogether
+            log::error!("Unable to load boosts and pauses: {:?}", error);
+            TimedOverrides::default()
+        }
+    };
+    let expired = timed_overrides.remove_expired(&now);
+    if !expired.is_empty() {
+        timed_overrides.save()?;
+        let _ = email_client.send_timed_overrides_ended(&expired);
+    }
+
+    let price_client = price_client(config, paths)?;
+    let today = now.date_naive();
+
+    let (mut schedule, created) = ensure_schedule(
+        today,
+        &price_client,
+        config,
+        email_client,
+        paths,
+        None,
+        &now,
+    )?;
     if created {
-        let _ = email_client.send_schedule(RelativeDate::Today.to_naive_date(), &schedule);
+        let _ = email_client.send_schedule(today, &schedule, &timed_overrides);
     }
 
     let tomorrow_available =
         NaiveTime::from_hms_opt(MAKE_TOMORROWS_SCHEDULE.0, MAKE_TOMORROWS_SCHEDULE.1, 0).unwrap();
     if now.time() >= tomorrow_available {
-        match ensure_schedule(RelativeDate::Tomorrow, &price_client, config) {
-            Ok((schedule, created)) => {
+        match ensure_schedule(
+            today + Days::new(1),
+            &price_client,
+            config,
+            email_client,
+            paths,
+            Some(&mut schedule),
+            &now,
+        ) {
+            Ok((tomorrows_schedule, created)) => {
                 if created {
                     email_client
-                        .send_schedule(RelativeDate::Tomorrow.to_naive_date(), &schedule)
+                        .send_schedule(today + Days::new(1), &tomorrows_schedule, &timed_overrides)
                         .unwrap_or_else(|error| {
                             log::error!("Failed to send schedule: {}", error);
                         });
@@ -83,49 +271,447 @@ fn run(config: &[ScheduleConfig], email_client: &EmailClient) -> Result<()> {
         }
     }
 
-    let expected_states = schedule
+    let schedule_configs = schedule
+        .pins
+        .iter()
+        .map(|pin_schedule| {
+            config
+                .schedules
+                .iter()
+                .find(|schedule_config| schedule_config.name == pin_schedule.name)
+        })
+        .collect::<Vec<_>>();
+    let mut states = schedule
+        .with_timed_overrides(&timed_overrides)
         .pins
         .iter()
-        .map(|pin_schedule| (pin_schedule.pin, pin_schedule.is_on(&now)))
+        .map(|pin_schedule| pin_schedule.is_on(&now))
         .collect::<Vec<_>>();
+    let kept_off = match config.max_load_kw {
+        Some(max_load_kw) => limit_load(&schedule_configs, &mut states, max_load_kw)
+            .into_iter()
+            .map(|i| schedule.pins[i].name.as_str())
+            .collect(),
+        None => Vec::new(),
+    };
 
-    match set_pin_states(&expected_states)? {
-        StateChange::None => (),
-        StateChange::Change {
-            changed_pins,
-            powered_on,
-        } => {
-            let changes = changed_pins
-                .into_iter()
-                .map(|i| {
-                    let pin_schedule = &schedule.pins[i];
-                    let (_, state) = expected_states[i];
-                    (&pin_schedule.name as &str, state)
-                })
-                .collect::<Vec<_>>();
-            email_client.send_pin_state_change(&changes, powered_on)?;
+    let describe = |index: usize, description: &Option<String>| {
+        let name = &schedule.pins[index].name;
+        match description {
+            Some(description) => format!("{} ({})", name, description),
+            None => name.clone(),
+        }
+    };
+    let mut outputs = Vec::new();
+    let mut errors = Vec::new();
+    for (index, pin_schedule) in schedule.pins.iter().enumerate() {
+        // Schedules removed from the config still control their outputs until the end of the day
+        let schedule_config = schedule_configs[index];
+        let output_configs = schedule_config.map_or(&pin_schedule.outputs[..], |schedule_config| {
+            schedule_config.outputs()
+        });
+        let grouped = output_configs.len() > 1;
+        for output_config in output_configs {
+            let description = grouped.then(|| output_config.to_string());
+            match actuator_for(output_config, config.simulated_gpio.as_deref()) {
+                Ok(actuator) => outputs.push(Output {
+                    schedule: index,
+                    description,
+                    actuator,
+                    on: states[index],
+                    priority: schedule_config.map_or(0, |schedule_config| schedule_config.priority),
+                }),
+                Err(error) => {
+                    errors.push(format!("{}: {:#}", describe(index, &description), error))
+                }
+            }
         }
     }
 
-    Ok(())
+    let (state_change, set_errors) = set_states(&outputs, config.stagger());
+    errors.extend(set_errors.iter().map(|(i, error)| {
+        let output = &outputs[*i];
+        format!(
+            "{}: {:#}",
+            describe(output.schedule, &output.description),
+            error
+        )
+    }));
+    if let StateChange::Change {
+        changed_outputs,
+        powered_on,
+    } = &state_change
+    {
+        let changes = changed_outputs
+            .iter()
+            .map(|&i| {
+                let output = &outputs[i];
+                OutputStateChange {
+                    name: &schedule.pins[output.schedule].name,
+                    output: output.description.clone(),
+                    on: output.on,
+                }
+            })
+            .collect::<Vec<_>>();
+        if let Err(error) = email_client.send_pin_state_change(&changes, &kept_off, *powered_on) {
+            if errors.is_empty() {
+                return Err(error);
+            }
+            log::error!("Failed to send state change: {}", error);
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(eyre!("Unable to set outputs\n{}", errors.join("\n")));
+    }
+    Ok(state_change)
+}
+
+/// Turns off the schedules with the lowest priority until the total power of the devices that
+/// are on is at most `max_load_kw`. Returns the indices of the schedules that were turned off.
+fn limit_load(
+    schedule_configs: &[Option<&ScheduleConfig>],
+    states: &mut [bool],
+    max_load_kw: f64,
+) -> Vec<usize> {
+    let priority = |i: usize| schedule_configs[i].map_or(0, |config| config.priority);
+    let mut order = (0..states.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| Reverse(priority(i)));
+
+    let mut load_kw = 0.0;
+    let mut kept_off = Vec::new();
+    for i in order {
+        let (true, Some(config)) = (states[i], schedule_configs[i]) else {
+            continue;
+        };
+        let power_kw = config.power_kw.unwrap_or(0.0);
+        if load_kw + power_kw > max_load_kw {
+            log::warn!(
+                "Keeping {} off to stay within {} kW",
+                config.name,
+                max_load_kw
+            );
+            states[i] = false;
+            kept_off.push(i);
+        } else {
+            load_kw += power_kw;
+        }
+    }
+    kept_off.sort();
+    kept_off
 }
 
+/// Loads the schedule for `date`, or computes it if it doesn't exist. The prices are reused from
+/// the price store if they have already been fetched, e.g. when the schedule file was removed to
+/// recompute it after changing the config. Pins with `rolling_window` set continue from the
+/// schedule of the day before, and `previous`, that schedule if given, is optimized together with
+/// the new one from `now` onwards and saved.
 fn ensure_schedule(
-    date: RelativeDate,
-    client: &PriceClient,
-    config: &[ScheduleConfig],
+    date: NaiveDate,
+    client: &BlockingPriceClient,
+    config: &Config,
+    email_client: &EmailClient,
+    paths: &Paths,
+    previous: Option<&mut Schedule>,
+    now: &DateTime<Local>,
 ) -> Result<(Schedule, bool)> {
-    if let Some(schedule) = Schedule::load_for_date(date.to_naive_date()) {
-        Ok((schedule, false))
-    } else {
-        log::info!("Getting prices for {}", date.to_naive_date());
-        let prices = client.get_prices_for_date(date)?;
-        // DST change day may only have 23 entries
-        if prices.len() < 23 {
-            return Err(eyre!("Incomplete prices for {}", date.to_naive_date()));
+    if let Some(schedule) = Schedule::load_from(&paths.schedules, date) {
+        return Ok((schedule, false));
+    }
+    let prices = get_prices(date, client, config, email_client)?;
+    let previous_date = date - Days::new(1);
+    let earlier = Schedule::load_from(&paths.schedules, previous_date - Days::new(1));
+    let mut schedule = match previous {
+        Some(previous) => {
+            let schedule = Schedule::compute_for_date(
+                config,
+                &prices.prices,
+                earlier.as_ref(),
+                Some(&mut *previous),
+                now,
+            );
+            previous.write_to(&paths.schedules, previous_date)?;
+            schedule
+        }
+        None => {
+            // The day before is over, so it is only continued from
+            let mut previous = Schedule::load_from(&paths.schedules, previous_date);
+            Schedule::compute_for_date(
+                config,
+                &prices.prices,
+                earlier.as_ref(),
+                previous.as_mut(),
+                now,
+            )
         }
-        let schedule = Schedule::compute(config, &prices);
-        schedule.write_to_file(date.to_naive_date())?;
-        Ok((schedule, true))
+    };
+    schedule.source = Some(prices.source.to_string());
+    schedule.write_to(&paths.schedules, date)?;
+    Ok((schedule, true))
+}
+
+/// Gets the prices and warns by email if they could not be confirmed from another source
+fn get_prices(
+    date: NaiveDate,
+    client: &BlockingPriceClient,
+    config: &Config,
+    email_client: &EmailClient,
+) -> Result<DayPrices> {
+    log::info!("Getting prices for {}", date);
+    let prices = client.get_prices_for_date(date, config.price_area, config.vat_percent)?;
+    if let Some(mismatch) = &prices.mismatch {
+        email_client
+            .send_price_mismatch(date, mismatch)
+            .unwrap_or_else(|error| {
+                log::error!("Failed to send price mismatch warning: {}", error);
+            });
+    }
+    Ok(prices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{limit_load, recompute, run, Paths};
+    use crate::actuator::StateChange;
+    use crate::gpio::SimulatedGpioActuator;
+    use chrono::{DateTime, Days, Local, NaiveDate, TimeZone};
+    use common::config::Config;
+    use common::email::EmailClient;
+    use common::overrides::{Action, TimedOverride, TimedOverrides};
+    use common::prices::{round_price, Price, PriceArea, StoredPrices};
+    use common::schedule::Schedule;
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    const CONFIG: &str = r#"{
+        "schedules": [
+            { "name": "Boiler", "pin": 17, "min_on_hours": 2, "max_on_hours": 2, "min_consecutive_on_hours": null },
+            {
+                "name": "Heater",
+                "outputs": [
+                    { "pin": 27 },
+                    { "pin": 22, "actuator": { "type": "gpio", "active_low": true } }
+                ],
+                "min_on_hours": 3,
+                "max_on_hours": 3,
+                "min_consecutive_on_hours": null
+            }
+        ],
+        "email": null,
+        "tariff": null
+    }"#;
+
+    /// Temporary directory that is removed when dropped, even if the test fails
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("sahko-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn paths(&self) -> Paths {
+            Paths {
+                schedules: self.0.join("schedules"),
+                overrides: self.0.join("overrides.json"),
+                prices: self.0.join("prices"),
+            }
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn at(date: NaiveDate, hour: u32) -> DateTime<Local> {
+        Local
+            .from_local_datetime(&date.and_hms_opt(hour, 0, 0).unwrap())
+            .unwrap()
+    }
+
+    /// Prices that are cheapest from 03:00 onwards
+    fn prices(date: NaiveDate) -> Vec<Price> {
+        (0..24)
+            .map(|hour| Price {
+                validity: at(date, hour).fixed_offset(),
+                price: ((hour + 21) % 24) as f64,
+            })
+            .collect()
+    }
+
+    /// Levels of the lines of the default chip
+    fn gpio_lines(path: &Path) -> BTreeMap<u8, bool> {
+        SimulatedGpioActuator::read_lines(path)
+            .unwrap()
+            .remove("/dev/gpiochip0")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_run_through_day() {
+        let dir = TempDir::new("run");
+        let paths = dir.paths();
+        let gpio = dir.0.join("gpio.json");
+        let mut config: Config = serde_json::from_str(CONFIG).unwrap();
+        config.simulated_gpio = Some(gpio.clone());
+        let email_client = EmailClient::new(&None);
+        let today = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let tomorrow = today + Days::new(1);
+        // Existing schedules, so that no prices are fetched
+        for date in [today, tomorrow] {
+            Schedule::compute(&config, &prices(date))
+                .write_to(&paths.schedules, date)
+                .unwrap();
+        }
+        let mut timed_overrides = TimedOverrides::load_from(&paths.overrides).unwrap();
+        timed_overrides.add(TimedOverride {
+            name: "Heater".to_string(),
+            action: Action::Boost,
+            start: at(today, 20).fixed_offset(),
+            end: at(today, 22).fixed_offset(),
+            author: "test".to_string(),
+        });
+        timed_overrides.save().unwrap();
+
+        let changes = (0..24)
+            .map(|hour| {
+                let change = run(&config, &email_client, &paths, at(today, hour)).unwrap();
+                (hour, change)
+            })
+            .filter(|(_, change)| *change != StateChange::None)
+            .collect::<Vec<_>>();
+        let change = |changed_outputs: Vec<usize>| StateChange::Change {
+            changed_outputs,
+            powered_on: false,
+        };
+        assert_eq!(
+            changes,
+            vec![
+                (
+                    0,
+                    StateChange::Change {
+                        changed_outputs: vec![0, 1, 2],
+                        powered_on: true,
+                    }
+                ),
+                (3, change(vec![0, 1, 2])),
+                (5, change(vec![0])),
+                (6, change(vec![1, 2])),
+                (20, change(vec![1, 2])),
+                (22, change(vec![1, 2])),
+            ]
+        );
+        // Pin 22 is active low
+        assert_eq!(
+            gpio_lines(&gpio),
+            BTreeMap::from([(17, false), (22, true), (27, false)])
+        );
+        assert!(TimedOverrides::load_from(&paths.overrides)
+            .unwrap()
+            .overrides
+            .is_empty());
+
+        // The states are kept across runs, like on a real chip
+        assert_eq!(
+            run(&config, &email_client, &paths, at(tomorrow, 3)).unwrap(),
+            change(vec![0, 1, 2])
+        );
+        assert_eq!(
+            gpio_lines(&gpio),
+            BTreeMap::from([(17, true), (22, false), (27, true)])
+        );
+    }
+
+    #[test]
+    fn test_run_with_corrupt_overrides() {
+        let dir = TempDir::new("corrupt-overrides");
+        let paths = dir.paths();
+        let mut config: Config = serde_json::from_str(CONFIG).unwrap();
+        config.simulated_gpio = Some(dir.0.join("gpio.json"));
+        let today = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        Schedule::compute(&config, &prices(today))
+            .write_to(&paths.schedules, today)
+            .unwrap();
+        std::fs::write(&paths.overrides, "[{").unwrap();
+
+        let change = run(&config, &EmailClient::new(&None), &paths, at(today, 3)).unwrap();
+        assert_eq!(
+            change,
+            StateChange::Change {
+                changed_outputs: vec![0, 1, 2],
+                powered_on: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_recompute_with_new_vat() {
+        let dir = TempDir::new("recompute-vat");
+        let paths = dir.paths();
+        let mut config: Config = serde_json::from_str(CONFIG).unwrap();
+        // A date in the future, so that no slots are kept as they were
+        let date = NaiveDate::from_ymd_opt(2099, 1, 15).unwrap();
+        let stored = StoredPrices {
+            source: "Stub".to_string(),
+            area: PriceArea::Fi,
+            prices: prices(date),
+        };
+        paths.price_store().save(date, &stored).unwrap();
+        let mut schedule = Schedule::compute(&config, &prices(date));
+        schedule.source = Some("Stub".to_string());
+        schedule.write_to(&paths.schedules, date).unwrap();
+
+        config.vat_percent = Some(20.0);
+        recompute(&config, &EmailClient::new(&None), &paths, Some(date)).unwrap();
+        let schedule = Schedule::load_from(&paths.schedules, date).unwrap();
+        let expected = stored
+            .prices
+            .iter()
+            .map(|price| round_price(price.price * 1.2))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            schedule
+                .prices
+                .iter()
+                .map(|price| price.price)
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(schedule.source.as_deref(), Some("Stub"));
+    }
+
+    #[test]
+    fn test_limit_load() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "schedules": [
+                { "name": "Sauna", "min_on_hours": 1, "max_on_hours": 1, "power_kw": 6.0 },
+                {
+                    "name": "Boiler",
+                    "min_on_hours": 1,
+                    "max_on_hours": 1,
+                    "power_kw": 3.0,
+                    "priority": 1
+                },
+                { "name": "Heater", "min_on_hours": 1, "max_on_hours": 1, "power_kw": 2.0 }
+            ],
+            "email": null,
+            "tariff": null,
+            "max_load_kw": 6.0
+        }))
+        .unwrap();
+        let schedule_configs = config.schedules.iter().map(Some).collect::<Vec<_>>();
+
+        // Boiler has the highest priority, and Heater still fits after Sauna is kept off
+        let mut states = vec![true, true, true];
+        assert_eq!(limit_load(&schedule_configs, &mut states, 6.0), vec![0]);
+        assert_eq!(states, vec![false, true, true]);
+
+        let mut states = vec![true, false, false];
+        assert!(limit_load(&schedule_configs, &mut states, 6.0).is_empty());
+        assert_eq!(states, vec![true, false, false]);
     }
 }
@@ -0,0 +1,191 @@
+use eyre::{eyre, Result, WrapErr};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::actuator::{Actuator, Transition};
+
+fn http_client() -> reqwest::blocking::Client {
+    reqwest::blocking::ClientBuilder::new()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap()
+}
+
+/// Shelly Gen2 relay, controlled with the `Switch.Set` RPC method
+pub struct ShellyActuator {
+    client: reqwest::blocking::Client,
+    url: String,
+    switch_id: u8,
+}
+
+impl ShellyActuator {
+    pub fn new(url: &str, switch_id: u8) -> Self {
+        Self {
+            client: http_client(),
+            url: url.trim_end_matches('/').to_string(),
+            switch_id,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ShellySetResponse {
+    was_on: bool,
+}
+
+impl Actuator for ShellyActuator {
+    fn set(&self, on: bool) -> Result<Transition> {
+        let response = self
+            .client
+            .get(format!("{}/rpc/Switch.Set", self.url))
+            .query(&[("id", self.switch_id.to_string()), ("on", on.to_string())])
+            .send()
+            .and_then(|response| response.error_for_status())
+            .wrap_err_with(|| format!("Unable to set Shelly relay {}", self.url))?
+            .json::<ShellySetResponse>()
+            .wrap_err("Unable to parse Shelly response")?;
+        Ok(if response.was_on == on {
+            Transition::Unchanged
+        } else {
+            Transition::Changed
+        })
+    }
+}
+
+/// Tasmota relay, controlled with `Power` commands
+pub struct TasmotaActuator {
+    client: reqwest::blocking::Client,
+    url: String,
+    relay: Option<u8>,
+}
+
+impl TasmotaActuator {
+    pub fn new(url: &str, relay: Option<u8>) -> Self {
+        Self {
+            client: http_client(),
+            url: url.trim_end_matches('/').to_string(),
+            relay,
+        }
+    }
+
+    /// Runs a command and returns the resulting state of the relay
+    fn command(&self, command: &str) -> Result<bool> {
+        let response = self
+            .client
+            .get(format!("{}/cm", self.url))
+            .query(&[("cmnd", command)])
+            .send()
+            .and_then(|response| response.error_for_status())
+            .wrap_err_with(|| format!("Unable to send command to Tasmota relay {}", self.url))?
+            .json::<HashMap<String, serde_json::Value>>()
+            .wrap_err("Unable to parse Tasmota response")?;
+        // Devices with one relay reply with POWER even if asked for POWER1
+        let state = [self.power().to_uppercase(), "POWER".to_string()]
+            .iter()
+            .find_map(|key| response.get(key))
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| eyre!("Unexpected Tasmota response {:?}", response))?;
+        Ok(state == "ON")
+    }
+
+    fn power(&self) -> String {
+        match self.relay {
+            Some(relay) => format!("Power{}", relay),
+            None => "Power".to_string(),
+        }
+    }
+}
+
+impl Actuator for TasmotaActuator {
+    fn set(&self, on: bool) -> Result<Transition> {
+        if self.command(&self.power())? == on {
+            return Ok(Transition::Unchanged);
+        }
+        let state = if on { "On" } else { "Off" };
+        if self.command(&format!("{} {}", self.power(), state))? != on {
+            return Err(eyre!("Tasmota relay {} did not turn {}", self.url, state));
+        }
+        Ok(Transition::Changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShellyActuator, TasmotaActuator};
+    use crate::actuator::{Actuator, Transition};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Serves one canned JSON response per request and records the requested paths
+    fn mock_server(responses: &[&'static str]) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let responses = responses.to_vec();
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for body in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap().to_string();
+                recorded.lock().unwrap().push(path);
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    #[test]
+    fn test_shelly() {
+        let (url, requests) = mock_server(&[r#"{"was_on": false}"#, r#"{"was_on": true}"#]);
+        let actuator = ShellyActuator::new(&url, 1);
+        assert_eq!(actuator.set(true).unwrap(), Transition::Changed);
+        assert_eq!(actuator.set(true).unwrap(), Transition::Unchanged);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                "/rpc/Switch.Set?id=1&on=true",
+                "/rpc/Switch.Set?id=1&on=true"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tasmota() {
+        let (url, requests) = mock_server(&[
+            r#"{"POWER2": "OFF"}"#,
+            r#"{"POWER2": "ON"}"#,
+            r#"{"POWER2": "ON"}"#,
+        ]);
+        let actuator = TasmotaActuator::new(&url, Some(2));
+        assert_eq!(actuator.set(true).unwrap(), Transition::Changed);
+        assert_eq!(actuator.set(true).unwrap(), Transition::Unchanged);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec!["/cm?cmnd=Power2", "/cm?cmnd=Power2+On", "/cm?cmnd=Power2"]
+        );
+    }
+
+    #[test]
+    fn test_tasmota_single_relay() {
+        let (url, _) = mock_server(&[r#"{"POWER": "ON"}"#, r#"{"POWER": "OFF"}"#]);
+        let actuator = TasmotaActuator::new(&url, None);
+        assert_eq!(actuator.set(false).unwrap(), Transition::Changed);
+    }
+}